@@ -1,17 +1,57 @@
 use crate::{
-    matching::{find_matching, IteratorOrder},
-    runtime::{Operator, Runner},
+    cell::{Arithmetic, Cell},
+    coverage::Coverage,
+    matching::jump_table,
+    profile::Profile,
+    runtime::{
+        flush_before_input, read_cell, write_cell, DebugDump, EofPolicy, FlushPolicy, Operator,
+        OutputMode, PointerPolicy, Runner, RuntimeError,
+    },
+    tape::Tape,
     token::BFToken,
+    trace::{TraceEvent, TraceHook},
 };
 
-use std::io::{BufRead, Write};
+use std::{
+    io::{BufRead, Write},
+    marker::PhantomData,
+};
 
+/// The reference runtime, executing one token at a time.
+///
+/// Memory is a fixed `Vec` of `u8` cells unless another [`Cell`] type is picked with
+/// [`BrainfuckRuntime::with_cells`] or another [`Tape`] with [`BrainfuckRuntime::with_tape`]:
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::<u16>::with_cells(30_000);
+/// runtime.arithmetic = Arithmetic::Checked;
+///
+/// let result = runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("-")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+///
+/// assert!(matches!(result, Err(RuntimeError::CellOverflow { instruction: 0 })));
+/// ```
 #[derive(Debug, Clone)]
-pub struct BrainfuckRuntime {
+pub struct BrainfuckRuntime<C: Cell = u8, T: Tape<C> = Vec<C>> {
     pub pointer: usize,
     pub instruction: usize,
     pub instruction_stack: Vec<BFToken>,
-    pub memory: Vec<u8>,
+    pub memory: T,
+    pub arithmetic: Arithmetic,
+    pub eof_policy: EofPolicy,
+    pub output_mode: OutputMode,
+    pub flush_policy: FlushPolicy,
+    pub pointer_policy: PointerPolicy,
+    pub debug_dump: Option<DebugDump>,
+    pub tracer: Option<TraceHook<C>>,
+    pub profile: Option<Profile>,
+    pub coverage: Option<Coverage>,
+    jump_table: Vec<Option<usize>>,
+    cell: PhantomData<C>,
 }
 
 impl BrainfuckRuntime {
@@ -21,15 +61,80 @@ impl BrainfuckRuntime {
     }
 
     /// Creates a Brainfuck runtime with a custom memory size.
-    /// * `size` - Number of cells in the runtime memory.
+    /// * `size` - Number of cells in the runtime memory. Programs fail with
+    ///   [`RuntimeError::PointerOutOfBounds`] on an empty memory.
     pub fn with_memory_size(size: usize) -> Self {
+        Self::with_cells(size)
+    }
+}
+
+impl<C: Cell> BrainfuckRuntime<C> {
+    /// Creates a Brainfuck runtime with a custom memory size and cell type.
+    /// * `size` - Number of cells in the runtime memory.
+    pub fn with_cells(size: usize) -> Self {
+        Self::with_tape(vec![C::default(); size])
+    }
+}
+
+impl<C: Cell, T: Tape<C>> BrainfuckRuntime<C, T> {
+    /// Creates a Brainfuck runtime backed by `tape`.
+    pub fn with_tape(tape: T) -> Self {
         Self {
             instruction: 0,
             pointer: 0,
-            memory: vec![0x00; size],
+            memory: tape,
+            arithmetic: Arithmetic::default(),
             instruction_stack: Vec::new(),
+            jump_table: Vec::new(),
+            cell: PhantomData,
+            eof_policy: EofPolicy::default(),
+            output_mode: OutputMode::default(),
+            flush_policy: FlushPolicy::default(),
+            pointer_policy: PointerPolicy::default(),
+            debug_dump: None,
+            tracer: None,
+            profile: None,
+            coverage: None,
         }
     }
+
+    /// Pairs up every bracket in the instruction stack so jumps don't need to search for their counterpart.
+    ///
+    /// This is already done by [`Runner::add_tokens`], it only needs to be called again after modifying
+    /// `instruction_stack` by hand.
+    pub fn compile(&mut self) -> Result<&mut Self, RuntimeError> {
+        self.jump_table = jump_table(
+            &self.instruction_stack,
+            BFToken::JumpForwards,
+            BFToken::JumpBackwards,
+        )
+        .map_err(|instruction| RuntimeError::UnmatchedBracket { instruction })?;
+        Ok(self)
+    }
+
+    fn add_to_cell(&mut self, amount: i32) -> Result<(), RuntimeError> {
+        self.memory[self.pointer] = self.memory[self.pointer]
+            .add(amount, self.arithmetic)
+            .ok_or(RuntimeError::CellOverflow {
+                instruction: self.instruction,
+            })?;
+        Ok(())
+    }
+
+    fn jump_target(&self) -> Result<usize, RuntimeError> {
+        self.jump_table
+            .get(self.instruction)
+            .copied()
+            .flatten()
+            .ok_or(RuntimeError::UnmatchedBracket {
+                instruction: self.instruction,
+            })
+    }
+
+    /// The pointer relative to the starting cell, which stays the same when the tape grows to the left.
+    fn position(&self) -> isize {
+        self.pointer as isize - self.memory.origin() as isize
+    }
 }
 
 impl Default for BrainfuckRuntime {
@@ -38,93 +143,167 @@ impl Default for BrainfuckRuntime {
     }
 }
 
-impl Runner for BrainfuckRuntime {
+impl<C: Cell, T: Tape<C>> Runner for BrainfuckRuntime<C, T> {
     fn clean_env(&mut self) -> &mut Self {
         self.instruction = 0;
         self.pointer = 0;
-        self.memory = vec![0x00; self.memory.len()];
+        self.memory.clear();
         self.instruction_stack = Vec::new();
+        self.jump_table = Vec::new();
         self
     }
     fn next_instruction(
         &mut self,
         reader: &mut impl BufRead,
         writer: &mut impl Write,
-    ) -> &mut Self {
-        match self.instruction_stack[self.instruction] {
-            BFToken::CellAdd => self.op_add_to_cell(),
-            BFToken::CellSubtract => self.op_sub_from_cell(),
-            BFToken::PtrLeft => self.op_ptr_left(),
-            BFToken::PtrRight => self.op_ptr_right(),
-            BFToken::Print => self.op_print_cell_as_char(writer),
-            BFToken::Input => self.op_input_to_cell(reader),
-            BFToken::JumpForwards => self.op_jump_forwards(),
-            BFToken::JumpBackwards => self.op_jump_backwards(),
+    ) -> Result<&mut Self, RuntimeError> {
+        let Some(&token) = self.instruction_stack.get(self.instruction) else {
+            return Ok(self);
+        };
+        let instruction = self.instruction;
+        // Every instruction works with the cell under the pointer, which an empty tape doesn't have
+        if self.memory.is_empty() {
+            return Err(RuntimeError::PointerOutOfBounds { instruction });
+        }
+        let before = self
+            .tracer
+            .as_ref()
+            .map(|_| (self.position(), self.memory[self.pointer].clone()));
+        match token {
+            BFToken::CellAdd => self.op_add_to_cell()?,
+            BFToken::CellSubtract => self.op_sub_from_cell()?,
+            BFToken::PtrLeft => self.op_ptr_left()?,
+            BFToken::PtrRight => self.op_ptr_right()?,
+            BFToken::Print => self.op_print_cell_as_char(writer)?,
+            BFToken::Input => {
+                flush_before_input(writer, self.flush_policy)?;
+                self.op_input_to_cell(reader)?
+            }
+            BFToken::JumpForwards => self.op_jump_forwards()?,
+            BFToken::JumpBackwards => self.op_jump_backwards()?,
+            BFToken::Debug => self.op_debug_dump()?,
             _ => {}
         }
+        if let Some(profile) = &mut self.profile {
+            profile.record(instruction);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(instruction);
+        }
         self.instruction += 1;
-        self
+        if let (Some(tracer), Some((pointer_before, cell_before))) = (&self.tracer, before) {
+            tracer.trace(&TraceEvent {
+                instruction,
+                token,
+                pointer_before,
+                pointer_after: self.position(),
+                cell_before,
+                cell_after: self.memory[self.pointer].clone(),
+            })?;
+        }
+        Ok(self)
     }
-    fn run_full_stack(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> &mut Self {
-        while self.instruction != self.instruction_stack.len() {
-            self.next_instruction(reader, writer);
+    fn run_full_stack(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<&mut Self, RuntimeError> {
+        while self.instruction < self.instruction_stack.len() {
+            if let Err(err) = self.next_instruction(reader, writer) {
+                writer.flush()?;
+                return Err(err);
+            }
         }
-        self
+        writer.flush()?;
+        Ok(self)
+    }
+    fn is_finished(&self) -> bool {
+        self.instruction >= self.instruction_stack.len()
+    }
+    fn wants_input(&self) -> bool {
+        self.instruction_stack.get(self.instruction) == Some(&BFToken::Input)
     }
-    fn add_tokens(&mut self, tokens: Vec<BFToken>) -> &mut Self {
+    fn add_tokens(&mut self, tokens: Vec<BFToken>) -> Result<&mut Self, RuntimeError> {
+        let previous_len = self.instruction_stack.len();
         self.instruction_stack.extend(tokens);
-        self
+
+        if let Err(err) = self.compile() {
+            self.instruction_stack.truncate(previous_len);
+            return Err(err);
+        }
+        Ok(self)
     }
 }
 
-impl Operator for BrainfuckRuntime {
-    fn op_add_to_cell(&mut self) {
-        self.memory[self.pointer] = self.memory[self.pointer].wrapping_add(1);
+impl<C: Cell, T: Tape<C>> Operator for BrainfuckRuntime<C, T> {
+    fn op_add_to_cell(&mut self) -> Result<(), RuntimeError> {
+        self.add_to_cell(1)
     }
-    fn op_sub_from_cell(&mut self) {
-        self.memory[self.pointer] = self.memory[self.pointer].wrapping_sub(1);
+    fn op_sub_from_cell(&mut self) -> Result<(), RuntimeError> {
+        self.add_to_cell(-1)
     }
-    fn op_ptr_left(&mut self) {
-        self.pointer -= 1;
+    fn op_ptr_left(&mut self) -> Result<(), RuntimeError> {
+        if self.pointer > 0 {
+            self.pointer -= 1;
+            return Ok(());
+        }
+        match self.pointer_policy {
+            PointerPolicy::Wrap if !self.memory.is_empty() => self.pointer = self.memory.len() - 1,
+            PointerPolicy::Clamp => {}
+            PointerPolicy::Grow if self.memory.grow_left() => {}
+            _ => {
+                return Err(RuntimeError::PointerOutOfBounds {
+                    instruction: self.instruction,
+                })
+            }
+        }
+        Ok(())
     }
-    fn op_ptr_right(&mut self) {
-        self.pointer += 1;
+    fn op_ptr_right(&mut self) -> Result<(), RuntimeError> {
+        if self.pointer + 1 < self.memory.len() {
+            self.pointer += 1;
+            return Ok(());
+        }
+        match self.pointer_policy {
+            PointerPolicy::Wrap => self.pointer = 0,
+            PointerPolicy::Clamp => {}
+            PointerPolicy::Grow if self.memory.grow_right() => self.pointer += 1,
+            _ => {
+                return Err(RuntimeError::PointerOutOfBounds {
+                    instruction: self.instruction,
+                })
+            }
+        }
+        Ok(())
     }
-    fn op_print_cell_as_char(&self, writer: &mut impl Write) {
-        write!(writer, "{}", self.memory[self.pointer] as char)
-            .expect("Error when writing data to writer");
-        writer.flush().unwrap();
+    fn op_print_cell_as_char(&self, writer: &mut impl Write) -> Result<(), RuntimeError> {
+        write_cell(
+            writer,
+            &self.memory[self.pointer],
+            self.output_mode,
+            self.flush_policy,
+        )?;
+        Ok(())
     }
-    fn op_input_to_cell(&mut self, reader: &mut impl BufRead) {
-        let mut input: String = String::new();
-
-        reader
-            .read_to_string(&mut input)
-            .expect("Failed to read line");
-        self.memory[self.pointer] = input.bytes().next().expect("no byte read");
-    }
-    fn op_jump_forwards(&mut self) {
-        if self.memory[self.pointer] == 0 {
-            self.instruction = find_matching(
-                &self.instruction_stack,
-                BFToken::JumpForwards,
-                BFToken::JumpBackwards,
-                self.instruction,
-                IteratorOrder::FrontToBack,
-            )
-            .expect("Matching bracket could not be found at instruction number {instruction}");
+    fn op_input_to_cell(&mut self, reader: &mut impl BufRead) -> Result<(), RuntimeError> {
+        read_cell(reader, &mut self.memory[self.pointer], self.eof_policy)
+    }
+    fn op_jump_forwards(&mut self) -> Result<(), RuntimeError> {
+        if self.memory[self.pointer].is_zero() {
+            self.instruction = self.jump_target()?;
+        }
+        Ok(())
+    }
+    fn op_jump_backwards(&mut self) -> Result<(), RuntimeError> {
+        if !self.memory[self.pointer].is_zero() {
+            self.instruction = self.jump_target()?;
         }
+        Ok(())
     }
-    fn op_jump_backwards(&mut self) {
-        if self.memory[self.pointer] != 0 {
-            self.instruction = find_matching(
-                &self.instruction_stack,
-                BFToken::JumpForwards,
-                BFToken::JumpBackwards,
-                self.instruction,
-                IteratorOrder::BackToFront,
-            )
-            .expect("Matching bracket could not be found at instruction number {instruction}");
+    fn op_debug_dump(&self) -> Result<(), RuntimeError> {
+        if let Some(debug_dump) = &self.debug_dump {
+            debug_dump.dump(self.instruction, self.pointer, &self.memory)?;
         }
+        Ok(())
     }
 }
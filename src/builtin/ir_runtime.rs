@@ -0,0 +1,191 @@
+use crate::{
+    ir::{compile, optimize, Instruction},
+    runtime::{
+        flush_before_input, read_cell, write_cell, EofPolicy, FlushPolicy, OutputMode, Runner,
+        RuntimeError,
+    },
+    token::BFToken,
+};
+
+use std::io::{BufRead, Write};
+
+/// A runtime that executes [`Instruction`]s instead of single tokens.
+///
+/// Tokens are compiled when they are added, so it can be used as a drop-in replacement for
+/// [`BrainfuckRuntime`](crate::builtin::bf_runtime::BrainfuckRuntime) while dispatching far fewer instructions
+/// for programs made of long runs of `+`, `-`, `<` and `>`. Errors while running report indices into `program`,
+/// while unbalanced brackets rejected by [`Runner::add_tokens`] report the index of the token that was added.
+///
+/// Cells are always `u8` with wrapping arithmetic. Setting `optimize` makes [`Runner::add_tokens`] use [`ir::optimize`](crate::ir::optimize) instead of
+/// [`ir::compile`](crate::ir::compile), collapsing common loop idioms as well.
+///
+/// # Example:
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut output: Vec<u8> = Vec::new();
+/// let mut runtime = IrRuntime::new();
+///
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("++++++++[>++++++++<-]>+.")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut output)
+///     .expect("Failed running program");
+///
+/// assert_eq!(output, b"A");
+/// ```
+#[derive(Debug, Clone)]
+pub struct IrRuntime {
+    pub pointer: usize,
+    pub instruction: usize,
+    pub program: Vec<Instruction>,
+    pub memory: Vec<u8>,
+    pub optimize: bool,
+    pub eof_policy: EofPolicy,
+    pub output_mode: OutputMode,
+    pub flush_policy: FlushPolicy,
+}
+
+impl IrRuntime {
+    /// Creates a new IR runtime with a default 30,000-cell memory.
+    pub fn new() -> Self {
+        Self::with_memory_size(30_000)
+    }
+
+    /// Creates an IR runtime with a custom memory size.
+    /// * `size` - Number of cells in the runtime memory.
+    pub fn with_memory_size(size: usize) -> Self {
+        Self {
+            instruction: 0,
+            pointer: 0,
+            memory: vec![0x00; size],
+            program: Vec::new(),
+            optimize: false,
+            eof_policy: EofPolicy::default(),
+            output_mode: OutputMode::default(),
+            flush_policy: FlushPolicy::default(),
+        }
+    }
+
+    /// Index of the cell `n` cells away from the pointer, if it is inside of the tape.
+    fn offset_pointer(&self, n: isize) -> Result<usize, RuntimeError> {
+        self.pointer
+            .checked_add_signed(n)
+            .filter(|pointer| *pointer < self.memory.len())
+            .ok_or(RuntimeError::PointerOutOfBounds {
+                instruction: self.instruction,
+            })
+    }
+}
+
+impl Default for IrRuntime {
+    fn default() -> Self {
+        IrRuntime::new()
+    }
+}
+
+impl Runner for IrRuntime {
+    fn clean_env(&mut self) -> &mut Self {
+        self.instruction = 0;
+        self.pointer = 0;
+        self.memory = vec![0x00; self.memory.len()];
+        self.program = Vec::new();
+        self
+    }
+    fn next_instruction(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<&mut Self, RuntimeError> {
+        let Some(instruction) = self.program.get(self.instruction) else {
+            return Ok(self);
+        };
+        if self.memory.is_empty() {
+            return Err(RuntimeError::PointerOutOfBounds {
+                instruction: self.instruction,
+            });
+        }
+        match *instruction {
+            Instruction::Add(n) => {
+                self.memory[self.pointer] = self.memory[self.pointer].wrapping_add(n as u8)
+            }
+            Instruction::Move(n) => {
+                self.pointer = self.offset_pointer(n)?;
+            }
+            Instruction::Print => {
+                write_cell(
+                    writer,
+                    &self.memory[self.pointer],
+                    self.output_mode,
+                    self.flush_policy,
+                )?;
+            }
+            Instruction::Input => {
+                flush_before_input(writer, self.flush_policy)?;
+                read_cell(reader, &mut self.memory[self.pointer], self.eof_policy)?
+            }
+            Instruction::Jz(target) => {
+                if self.memory[self.pointer] == 0 {
+                    self.instruction = target;
+                }
+            }
+            Instruction::Jnz(target) => {
+                if self.memory[self.pointer] != 0 {
+                    self.instruction = target;
+                }
+            }
+            Instruction::Clear => self.memory[self.pointer] = 0,
+            Instruction::Scan(n) => {
+                while self.memory[self.pointer] != 0 {
+                    self.pointer = self.offset_pointer(n)?;
+                }
+            }
+            Instruction::MulAdd { offset, factor } => {
+                let value = self.memory[self.pointer];
+                if value != 0 {
+                    let target = self.offset_pointer(offset)?;
+                    self.memory[target] =
+                        self.memory[target].wrapping_add(value.wrapping_mul(factor as u8));
+                }
+            }
+        }
+        self.instruction += 1;
+        Ok(self)
+    }
+    fn run_full_stack(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<&mut Self, RuntimeError> {
+        while self.instruction < self.program.len() {
+            if let Err(err) = self.next_instruction(reader, writer) {
+                writer.flush()?;
+                return Err(err);
+            }
+        }
+        writer.flush()?;
+        Ok(self)
+    }
+    fn is_finished(&self) -> bool {
+        self.instruction >= self.program.len()
+    }
+    fn wants_input(&self) -> bool {
+        self.program.get(self.instruction) == Some(&Instruction::Input)
+    }
+    fn add_tokens(&mut self, tokens: Vec<BFToken>) -> Result<&mut Self, RuntimeError> {
+        let offset = self.program.len();
+        let compiled = if self.optimize {
+            optimize(&tokens)?
+        } else {
+            compile(&tokens)?
+        };
+        self.program
+            .extend(compiled.into_iter().map(|instruction| match instruction {
+                Instruction::Jz(target) => Instruction::Jz(target + offset),
+                Instruction::Jnz(target) => Instruction::Jnz(target + offset),
+                other => other,
+            }));
+        Ok(self)
+    }
+}
@@ -7,14 +7,14 @@ use crate::token::*;
 ///
 /// Consists of 8 instructions:
 ///
-/// > + - Increment the memory cell under the pointer
-/// > - - Decrement the memory cell under the pointer
-/// > > - Move the pointer to the right
-/// > < - Move the pointer to the left
-/// > [ - Jump past the matching bracket if the cell under the pointer is 0
-/// > ] - Jump back to the matching bracket
-/// > . - Output the character signified by the cell at the pointer
-/// > , - Input a character and store it in the cell at the pointer
+/// > `+` - Increment the memory cell under the pointer
+/// > `-` - Decrement the memory cell under the pointer
+/// > `>` - Move the pointer to the right
+/// > `<` - Move the pointer to the left
+/// > `[` - Jump past the matching bracket if the cell under the pointer is 0
+/// > `]` - Jump back to the matching bracket
+/// > `.` - Output the character signified by the cell at the pointer
+/// > `,` - Input a character and store it in the cell at the pointer
 ///
 /// Example:
 ///
@@ -31,10 +31,32 @@ use crate::token::*;
 /// let mut std_brainfuck_app = BrainfuckRuntime::new();
 /// std_brainfuck_app.add_tokens(Brainfuck::to_tokens(program)
 ///      .expect("Failed parsing program"))
+///      .expect("Failed loading program")
 ///      .run_full_stack(&mut stdin().lock(), &mut stdout())
+///      .expect("Failed running program")
 ///      .clean_env();
 /// ```
 pub struct Brainfuck;
+/// Standard brainfuck with the `#` debugging extension
+///
+/// Same as [`Brainfuck`], but `#` becomes [`BFToken::Debug`], which dumps the tape around the pointer if the
+/// runtime has a [`DebugDump`](crate::runtime::DebugDump) set up and does nothing otherwise.
+///
+/// # Example:
+///
+/// ```rust
+/// use libbfi::prelude::*;
+/// use std::io::{stdin,stdout};
+///
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime.debug_dump = Some(DebugDump::stderr(4));
+///
+/// runtime.add_tokens(DebugBrainfuck::to_tokens(String::from("+++>++#<-#")).expect("Failed parsing program"))
+///      .expect("Failed loading program")
+///      .run_full_stack(&mut stdin().lock(), &mut stdout())
+///      .expect("Failed running program");
+/// ```
+pub struct DebugBrainfuck;
 /// A Ook brainfuck derivative interpreter
 ///
 /// Consists of 8 instructions:
@@ -58,7 +80,9 @@ pub struct Brainfuck;
 /// let mut monke = BrainfuckRuntime::new();
 ///
 /// monke.add_tokens(Ook::to_tokens(program).expect("Failed parsing program"))
+///      .expect("Failed loading program")
 ///      .run_full_stack(&mut stdin().lock(), &mut stdout())
+///      .expect("Failed running program")
 ///      .clean_env();
 /// ```
 pub struct Ook;
@@ -86,7 +110,9 @@ pub struct Ook;
 /// let mut fishe = BrainfuckRuntime::new();
 ///
 /// fishe.add_tokens(Blub::to_tokens(program).expect("Failed parsing program"))
+///      .expect("Failed loading program")
 ///      .run_full_stack(&mut stdin().lock(), &mut stdout())
+///      .expect("Failed running program")
 ///      .clean_env();
 /// ```
 pub struct Blub;
@@ -96,7 +122,14 @@ impl Tokenizer for Brainfuck {
     token_to_string!("+", "-", "<", ">", ".", ",", "[", "]");
 }
 
+impl Tokenizer for DebugBrainfuck {
+    single_char_tokenizer!(char, '+', '-', '<', '>', '.', ',', '[', ']', debug: '#');
+    token_to_string!("+", "-", "<", ">", ".", ",", "[", "]", debug: "#");
+}
+
 impl Tokenizer for Ook {
+    const SEPARATOR: &'static str = " ";
+
     multi_char_tokenizer!(str, 2, ".!?", "..", "!!", "?.", ".?", "!.", ".!", "!?", "?!");
     token_to_string!(
         "Ook. Ook.",
@@ -111,6 +144,8 @@ impl Tokenizer for Ook {
 }
 
 impl Tokenizer for Blub {
+    const SEPARATOR: &'static str = " ";
+
     multi_char_tokenizer!(str, 2, ".!?", "..", "!!", "?.", ".?", "!.", ".!", "!?", "?!");
     token_to_string!(
         "Blub. Blub.",
@@ -0,0 +1,115 @@
+#![doc = r"Cell types that can be used as the tape of a runtime"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use std::fmt::{Debug, Display};
+
+/// What happens when adding to or subtracting from a cell goes past its limits.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Arithmetic {
+    /// Wrap around to the other end (255 + 1 = 0 for 8-bit cells)
+    #[default]
+    Wrapping,
+    /// Stay at the limit (255 + 1 = 255 for 8-bit cells)
+    Saturating,
+    /// Stop with [`RuntimeError::CellOverflow`](crate::runtime::RuntimeError::CellOverflow)
+    Checked,
+}
+
+/// A single memory cell of the tape.
+///
+/// Implemented for `u8`, `u16`, `u32`, `u64`, `i32` and, with the `bignum` feature, for unbounded
+/// [`BigInt`](num_bigint::BigInt) cells. `Default` has to be the value of an empty cell (0).
+pub trait Cell: Clone + Default + PartialEq + Debug + Display {
+    /// Identifies the cell type in snapshots, so they can't be restored with a different one.
+    const NAME: &'static str;
+    /// Adds `amount` (negative values subtract) to the cell, `None` if the result does not fit in the cell
+    /// under [`Arithmetic::Checked`].
+    fn add(&self, amount: i32, arithmetic: Arithmetic) -> Option<Self>;
+    /// The cell holding the value of a byte read from input.
+    fn from_byte(byte: u8) -> Self;
+    /// The lowest 8 bits of the cell (two's complement for negative values), used for byte output.
+    fn low_byte(&self) -> u8;
+    /// The value of the cell if it can be used as a Unicode code point.
+    fn code_point(&self) -> Option<u32>;
+    /// Appends the cell to a binary snapshot.
+    fn encode(&self, out: &mut Vec<u8>);
+    /// Reads a cell written by [`Cell::encode`], advancing `input` past it.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+macro_rules! primitive_cell {
+    ($($type: ty),*) => {
+        $(
+            impl Cell for $type {
+                const NAME: &'static str = stringify!($type);
+
+                fn add(&self, amount: i32, arithmetic: Arithmetic) -> Option<Self> {
+                    let result = *self as i128 + amount as i128;
+                    match arithmetic {
+                        Arithmetic::Wrapping => Some(result as $type),
+                        Arithmetic::Saturating => {
+                            Some(result.clamp(<$type>::MIN as i128, <$type>::MAX as i128) as $type)
+                        }
+                        Arithmetic::Checked => <$type>::try_from(result).ok(),
+                    }
+                }
+                fn from_byte(byte: u8) -> Self {
+                    <$type>::from(byte)
+                }
+                fn low_byte(&self) -> u8 {
+                    *self as u8
+                }
+                fn code_point(&self) -> Option<u32> {
+                    u32::try_from(*self).ok()
+                }
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+                fn decode(input: &mut &[u8]) -> Option<Self> {
+                    let (bytes, rest) = input.split_first_chunk()?;
+                    *input = rest;
+                    Some(<$type>::from_le_bytes(*bytes))
+                }
+            }
+        )*
+    };
+}
+
+primitive_cell!(u8, u16, u32, u64, i32);
+
+#[cfg(feature = "bignum")]
+#[cfg_attr(docsrs, doc(cfg(feature = "bignum")))]
+impl Cell for num_bigint::BigInt {
+    const NAME: &'static str = "bigint";
+
+    /// Unbounded cells never overflow, so `arithmetic` is ignored.
+    fn add(&self, amount: i32, _arithmetic: Arithmetic) -> Option<Self> {
+        Some(self + amount)
+    }
+    fn from_byte(byte: u8) -> Self {
+        Self::from(byte)
+    }
+    fn low_byte(&self) -> u8 {
+        self.to_signed_bytes_le()[0]
+    }
+    fn code_point(&self) -> Option<u32> {
+        u32::try_from(self).ok()
+    }
+    fn encode(&self, out: &mut Vec<u8>) {
+        let bytes = self.to_signed_bytes_le();
+        crate::encoding::write_varint(out, bytes.len() as u64);
+        out.extend_from_slice(&bytes);
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::try_from(crate::encoding::read_varint(input)?).ok()?;
+        if input.len() < len {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(len);
+        *input = rest;
+        Some(Self::from_signed_bytes_le(bytes))
+    }
+}
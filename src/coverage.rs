@@ -0,0 +1,128 @@
+#![doc = r"Collecting which instructions and loop bodies ran, with lcov export"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{
+    profile::Profile,
+    token::{BFToken, SpannedToken},
+};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Instructions executed by a runtime whose `coverage` is set.
+///
+/// The hits are counted by a [`Profile`], so the profile of a run can also be turned into coverage with
+/// [`Coverage::from`]. Coverage is kept across [`Runner::clean_env`](crate::runtime::Runner::clean_env), so the same program can
+/// be run against a whole test suite before exporting it. Coverage from several runtimes can be combined with
+/// [`Coverage::merge`].
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let tokens = Brainfuck::to_tokens(String::from(",[.,]")).expect("Failed parsing program");
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime.coverage = Some(Coverage::new());
+/// runtime.eof_policy = EofPolicy::Zero;
+///
+/// runtime
+///     .add_tokens(tokens.clone())
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+///     .expect("Failed running program");
+///
+/// let coverage = runtime.coverage.as_ref().unwrap();
+/// assert!(!coverage.is_covered(2));
+/// assert_eq!(coverage.loop_bodies(&tokens), vec![(1, 4, false)]);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Coverage {
+    /// How many times each instruction ran
+    pub profile: Profile,
+}
+
+impl From<Profile> for Coverage {
+    fn from(profile: Profile) -> Self {
+        Self { profile }
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the hits of `other`, which must have been collected on the same program.
+    pub fn merge(&mut self, other: &Coverage) -> &mut Self {
+        self.profile.merge(&other.profile);
+        self
+    }
+
+    /// Whether the instruction at `instruction` ever ran.
+    pub fn is_covered(&self, instruction: usize) -> bool {
+        self.profile.count(instruction) > 0
+    }
+
+    /// Every loop in `tokens` as `(start, end, covered)` in source order, where `covered` tells if its body
+    /// ever ran.
+    pub fn loop_bodies(&self, tokens: &[BFToken]) -> Vec<(usize, usize, bool)> {
+        let mut loops: Vec<(usize, usize, bool)> = self
+            .profile
+            .loops(tokens)
+            .into_iter()
+            .map(|profile| {
+                (
+                    profile.start,
+                    profile.end,
+                    self.is_covered(profile.start + 1),
+                )
+            })
+            .collect();
+        loops.sort_unstable();
+        loops
+    }
+
+    /// Exports the coverage as an lcov tracefile record for `source_file`.
+    ///
+    /// `tokens` are the spanned tokens of the program as returned by
+    /// [`Tokenizer::to_spanned_tokens`](crate::token::Tokenizer::to_spanned_tokens). Lines report the highest
+    /// hit count of their instructions and every loop is reported as a branch on the line of its `[`, taken
+    /// when its body ran. Records of several files can be concatenated into a single tracefile.
+    pub fn to_lcov(&self, source_file: &str, tokens: &[SpannedToken]) -> String {
+        let hits = |instruction: usize| self.profile.count(instruction);
+
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+        for (instruction, spanned) in tokens.iter().enumerate() {
+            let line = lines.entry(spanned.start.line).or_insert(0);
+            *line = (*line).max(hits(instruction));
+        }
+
+        let mut record = format!("TN:\nSF:{source_file}\n");
+        let mut branches_hit = 0;
+        let bare: Vec<BFToken> = tokens.iter().map(|spanned| spanned.token).collect();
+        let loops = self.loop_bodies(&bare);
+        for (block, (start, _, covered)) in loops.iter().enumerate() {
+            let taken = if hits(*start) == 0 {
+                String::from("-")
+            } else {
+                hits(start + 1).to_string()
+            };
+            branches_hit += usize::from(*covered);
+            let _ = writeln!(
+                record,
+                "BRDA:{},{block},0,{taken}",
+                tokens[*start].start.line
+            );
+        }
+        let _ = writeln!(record, "BRF:{}\nBRH:{branches_hit}", loops.len());
+
+        for (line, hits) in &lines {
+            let _ = writeln!(record, "DA:{line},{hits}");
+        }
+        let lines_hit = lines.values().filter(|hits| **hits > 0).count();
+        let _ = writeln!(record, "LF:{}\nLH:{lines_hit}", lines.len());
+        record += "end_of_record\n";
+        record
+    }
+
+    pub(crate) fn record(&mut self, instruction: usize) {
+        self.profile.record(instruction);
+    }
+}
@@ -0,0 +1,387 @@
+#![doc = r"Breakpoints and stepping on top of a runtime"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{
+    builtin::bf_runtime::BrainfuckRuntime,
+    cell::Cell,
+    matching::{find_enclosing, find_matching, IteratorOrder},
+    runtime::{Runner, RuntimeError},
+    tape::Tape,
+    token::BFToken,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Read, Write},
+};
+
+/// A condition checked before every instruction while the debugger runs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Breakpoint<C: Cell = u8> {
+    /// The instruction at this index is about to be executed
+    Instruction(usize),
+    /// The cell at `index` holds `value`
+    Cell { index: usize, value: C },
+    /// The pointer is at this index
+    Pointer(usize),
+    /// An instruction of this type is about to be executed
+    Token(BFToken),
+}
+
+/// Why the debugger handed control back.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StopReason {
+    /// The requested step is complete
+    Step,
+    /// The breakpoint with this id was hit, the instruction it points at has not been executed yet
+    Breakpoint(usize),
+    /// Every instruction was executed
+    Finished,
+}
+
+/// Undo information for a single executed instruction, recorded by [`Debugger::record_history`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HistoryEntry<C: Cell = u8> {
+    /// Index of the executed instruction, where execution continues from after undoing it
+    pub instruction: usize,
+    /// Pointer before the instruction
+    pub pointer: usize,
+    /// Tape origin before the instruction, used to tell if the tape grew to the left since
+    pub origin: usize,
+    /// Value of the cell under the pointer before the instruction, if the instruction changed it
+    pub cell: Option<C>,
+    /// Byte consumed from the reader, if any
+    pub input: Option<u8>,
+}
+
+/// Wraps a [`BrainfuckRuntime`] with breakpoints and loop-aware stepping.
+///
+/// With [`Debugger::record_history`] enabled, execution can also be rewound with [`Debugger::step_back`] and
+/// [`Debugger::rewind_to_breakpoint`]. Input consumed by the rewound instructions is read again when running
+/// forward, output can't be taken back.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("+++[>++<-]>.")).expect("Failed parsing program"))
+///     .expect("Failed loading program");
+///
+/// let mut debugger = Debugger::new(runtime);
+/// let id = debugger.add_breakpoint(Breakpoint::Cell { index: 1, value: 4 });
+///
+/// let (input, output) = (&mut std::io::empty(), &mut std::io::sink());
+/// assert_eq!(debugger.resume(input, output).expect("Failed running program"), StopReason::Breakpoint(id));
+/// assert_eq!(debugger.runtime.memory[..2], [2, 4]);
+///
+/// debugger.remove_breakpoint(id);
+/// assert_eq!(debugger.step_out(input, output).expect("Failed running program"), StopReason::Step);
+/// assert_eq!(debugger.runtime.memory[..2], [0, 6]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debugger<C: Cell = u8, T: Tape<C> = Vec<C>> {
+    pub runtime: BrainfuckRuntime<C, T>,
+    breakpoints: Vec<Option<Breakpoint<C>>>,
+    history: VecDeque<HistoryEntry<C>>,
+    history_limit: usize,
+    replay: VecDeque<u8>,
+}
+
+impl<C: Cell, T: Tape<C>> Debugger<C, T> {
+    pub fn new(runtime: BrainfuckRuntime<C, T>) -> Self {
+        Self {
+            runtime,
+            breakpoints: Vec::new(),
+            history: VecDeque::new(),
+            history_limit: 0,
+            replay: VecDeque::new(),
+        }
+    }
+
+    /// Starts recording undo information for the last `limit` executed instructions, 0 turns recording off.
+    pub fn record_history(&mut self, limit: usize) -> &mut Self {
+        self.history_limit = limit;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+        self
+    }
+
+    /// Recorded instructions, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &HistoryEntry<C>> {
+        self.history.iter()
+    }
+
+    /// Undoes up to `steps` instructions, returning how many were undone.
+    ///
+    /// A tape that grew while running forward keeps its size, the extra cells are just left empty. If the tape
+    /// lost cells since the history was recorded (e.g.: the runtime was cleaned or restored), the history is
+    /// dropped and nothing more is undone.
+    pub fn step_back(&mut self, steps: usize) -> usize {
+        (0..steps).take_while(|_| self.undo()).count()
+    }
+
+    /// Undoes instructions until the state matches a breakpoint or the recorded history runs out, in which
+    /// case [`StopReason::Step`] is returned.
+    pub fn rewind_to_breakpoint(&mut self) -> StopReason {
+        while self.undo() {
+            if let Some(id) = self.hit_breakpoint() {
+                return StopReason::Breakpoint(id);
+            }
+        }
+        StopReason::Step
+    }
+
+    /// Adds a breakpoint, returning the id reported in [`StopReason::Breakpoint`] when it is hit.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint<C>) -> usize {
+        self.breakpoints.push(Some(breakpoint));
+        self.breakpoints.len() - 1
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> Option<Breakpoint<C>> {
+        self.breakpoints.get_mut(id)?.take()
+    }
+
+    /// Every active breakpoint alongside its id.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint<C>)> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(id, breakpoint)| Some((id, breakpoint.as_ref()?)))
+    }
+
+    /// Executes a single instruction, ignoring breakpoints.
+    pub fn step(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<StopReason, RuntimeError> {
+        if !self.runtime.is_finished() {
+            self.execute(reader, writer)?;
+        }
+        writer.flush()?;
+        Ok(self.stop_reason(StopReason::Step))
+    }
+
+    /// Runs until a breakpoint is hit or the program ends.
+    pub fn resume(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<StopReason, RuntimeError> {
+        self.run_to(reader, writer, None)
+    }
+
+    /// Executes the next instruction, and if it starts a loop, runs until the loop is done.
+    pub fn step_over(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<StopReason, RuntimeError> {
+        let stack = &self.runtime.instruction_stack;
+        let instruction = self.runtime.instruction;
+
+        match stack.get(instruction) {
+            Some(BFToken::JumpForwards) => {
+                let end = find_matching(
+                    stack,
+                    BFToken::JumpForwards,
+                    BFToken::JumpBackwards,
+                    instruction,
+                    IteratorOrder::FrontToBack,
+                )
+                .ok_or(RuntimeError::UnmatchedBracket { instruction })?;
+                self.run_to(reader, writer, Some(end + 1))
+            }
+            _ => self.step(reader, writer),
+        }
+    }
+
+    /// Runs until the loop holding the next instruction is done, or the program ends if it is not in a loop.
+    pub fn step_out(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<StopReason, RuntimeError> {
+        let stack = &self.runtime.instruction_stack;
+        let instruction = self.runtime.instruction;
+
+        let Some(start) = find_enclosing(
+            stack,
+            BFToken::JumpForwards,
+            BFToken::JumpBackwards,
+            instruction,
+        ) else {
+            return self.resume(reader, writer);
+        };
+        let end = find_matching(
+            stack,
+            BFToken::JumpForwards,
+            BFToken::JumpBackwards,
+            start,
+            IteratorOrder::FrontToBack,
+        )
+        .ok_or(RuntimeError::UnmatchedBracket { instruction: start })?;
+        self.run_to(reader, writer, Some(end + 1))
+    }
+
+    /// Id of the first breakpoint matching the current state of the runtime.
+    pub fn hit_breakpoint(&self) -> Option<usize> {
+        let runtime = &self.runtime;
+        let token = runtime.instruction_stack.get(runtime.instruction);
+
+        self.breakpoints().find_map(|(id, breakpoint)| {
+            let hit = match breakpoint {
+                Breakpoint::Instruction(index) => runtime.instruction == *index,
+                Breakpoint::Cell { index, value } => {
+                    *index < runtime.memory.len() && runtime.memory[*index] == *value
+                }
+                Breakpoint::Pointer(index) => runtime.pointer == *index,
+                Breakpoint::Token(expected) => token == Some(expected),
+            };
+            hit.then_some(id)
+        })
+    }
+
+    /// Always executes at least one instruction, so resuming from a breakpoint doesn't hit it again.
+    fn run_to(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+        target: Option<usize>,
+    ) -> Result<StopReason, RuntimeError> {
+        let reason = loop {
+            if self.runtime.is_finished() {
+                break StopReason::Finished;
+            }
+            if let Err(err) = self.execute(reader, writer) {
+                writer.flush()?;
+                return Err(err);
+            }
+            if target == Some(self.runtime.instruction) {
+                break StopReason::Step;
+            }
+            if let Some(id) = self.hit_breakpoint() {
+                break StopReason::Breakpoint(id);
+            }
+        };
+
+        writer.flush()?;
+        Ok(self.stop_reason(reason))
+    }
+
+    /// Executes the next instruction, reading replayed input first and recording history if enabled.
+    fn execute(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<(), RuntimeError> {
+        let runtime = &self.runtime;
+        let changes_cell = matches!(
+            runtime.instruction_stack.get(runtime.instruction),
+            Some(BFToken::CellAdd | BFToken::CellSubtract | BFToken::Input)
+        );
+        let mut entry = HistoryEntry {
+            instruction: runtime.instruction,
+            pointer: runtime.pointer,
+            origin: runtime.memory.origin(),
+            cell: changes_cell.then(|| runtime.memory[runtime.pointer].clone()),
+            input: None,
+        };
+
+        let mut input = ReplayReader {
+            replay: &mut self.replay,
+            reader,
+            consumed: None,
+        };
+        self.runtime.next_instruction(&mut input, writer)?;
+        entry.input = input.consumed;
+
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+            self.history.push_back(entry);
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(entry) = self.history.pop_back() else {
+            return false;
+        };
+        // The history only holds if the tape kept every cell it had, which isn't the case anymore if the runtime
+        // was cleaned or restored by hand since
+        let pointer = self
+            .runtime
+            .memory
+            .origin()
+            .checked_sub(entry.origin)
+            .map(|shift| entry.pointer + shift)
+            .filter(|pointer| *pointer < self.runtime.memory.len());
+        let Some(pointer) = pointer else {
+            self.history.clear();
+            return false;
+        };
+
+        self.runtime.instruction = entry.instruction;
+        self.runtime.pointer = pointer;
+        if let Some(cell) = entry.cell {
+            self.runtime.memory[pointer] = cell;
+        }
+        if let Some(byte) = entry.input {
+            self.replay.push_front(byte);
+        }
+        true
+    }
+
+    fn stop_reason(&self, reason: StopReason) -> StopReason {
+        if self.runtime.is_finished() {
+            StopReason::Finished
+        } else {
+            reason
+        }
+    }
+}
+
+/// Serves bytes given back by rewinding before the actual reader, remembering the last byte consumed.
+struct ReplayReader<'a, R: BufRead> {
+    replay: &'a mut VecDeque<u8>,
+    reader: &'a mut R,
+    consumed: Option<u8>,
+}
+
+impl<R: BufRead> Read for ReplayReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for ReplayReader<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.replay.is_empty() {
+            self.reader.fill_buf()
+        } else {
+            Ok(self.replay.as_slices().0)
+        }
+    }
+    fn consume(&mut self, amount: usize) {
+        if amount == 0 {
+            return;
+        }
+        if self.replay.is_empty() {
+            self.consumed = self
+                .reader
+                .fill_buf()
+                .ok()
+                .and_then(|buffer| buffer.get(amount - 1).copied());
+            self.reader.consume(amount);
+        } else {
+            self.consumed = self.replay.drain(..amount).next_back();
+        }
+    }
+}
@@ -0,0 +1,532 @@
+#![doc = r"Trivial brainfuck substitutions defined at runtime"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::token::{
+    check_brackets, positioned_chars, BFToken, Dialect, ParseErrorKind, SourcePosition,
+    SpannedToken, TokenParseError,
+};
+use std::{error::Error, fmt};
+#[cfg(any(feature = "toml", feature = "json"))]
+use std::{fs, path::Path};
+
+/// Tokens in the order their lexemes are given to [`SubstitutionDialect::new`].
+const TOKEN_ORDER: [BFToken; 8] = [
+    BFToken::CellAdd,
+    BFToken::CellSubtract,
+    BFToken::PtrLeft,
+    BFToken::PtrRight,
+    BFToken::Print,
+    BFToken::Input,
+    BFToken::JumpForwards,
+    BFToken::JumpBackwards,
+];
+
+/// Why a [`SubstitutionDialect`] could not be built.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DialectError {
+    /// The lexeme for this token is empty (or only whitespace)
+    EmptyLexeme(BFToken),
+    /// Two tokens share this lexeme
+    DuplicateLexeme(String),
+    /// A spec file could not be parsed or describes an unusable dialect, the reason is included
+    InvalidSpec(String),
+    /// A spec file could not be read, the reason is included
+    Unreadable(String),
+}
+
+impl fmt::Display for DialectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialectError::EmptyLexeme(token) => write!(f, "empty lexeme for {token:?}"),
+            DialectError::DuplicateLexeme(lexeme) => {
+                write!(f, "lexeme `{lexeme}` is used for more than one token")
+            }
+            DialectError::InvalidSpec(reason) => write!(f, "invalid dialect spec: {reason}"),
+            DialectError::Unreadable(reason) => write!(f, "unreadable dialect spec: {reason}"),
+        }
+    }
+}
+
+impl Error for DialectError {}
+
+/// Declarative description of a [`SubstitutionDialect`], meant to be shipped as a TOML or JSON file.
+///
+/// With the `toml` or `json` feature it can be read with [`DialectSpec::from_toml`] or
+/// [`DialectSpec::from_json`], or loaded straight into a dialect with [`SubstitutionDialect::load`]:
+///
+/// ```toml
+/// name = "Meow"
+/// case_insensitive = true
+/// whole_words = true
+/// line_comment = "#"
+/// block_comment = ["(=^", "^=)"]
+///
+/// [lexemes]
+/// cell_add = "meow"
+/// cell_subtract = "hiss"
+/// ptr_left = "paw"
+/// ptr_right = "pounce"
+/// print = "purr"
+/// input = "sniff"
+/// jump_forwards = "nap"
+/// jump_backwards = "wake"
+/// ```
+///
+/// Only `name` and `lexemes` are required.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct DialectSpec {
+    pub name: String,
+    pub lexemes: DialectLexemes,
+    /// Put between two lexemes when rendering, picked from the lexemes if missing
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub separator: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub case_insensitive: bool,
+    /// Only match lexemes that aren't part of a longer word
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub whole_words: bool,
+    /// Starts a comment that runs until the end of the line
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub line_comment: Option<String>,
+    /// Start and end of a comment that can span several lines
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub block_comment: Option<(String, String)>,
+}
+
+/// The lexeme of every instruction in a [`DialectSpec`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct DialectLexemes {
+    pub cell_add: String,
+    pub cell_subtract: String,
+    pub ptr_left: String,
+    pub ptr_right: String,
+    pub print: String,
+    pub input: String,
+    pub jump_forwards: String,
+    pub jump_backwards: String,
+}
+
+impl DialectSpec {
+    /// Reads a spec written in TOML.
+    #[cfg(feature = "toml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
+    pub fn from_toml(source: &str) -> Result<Self, DialectError> {
+        toml::from_str(source).map_err(|err| DialectError::InvalidSpec(err.to_string()))
+    }
+
+    /// Reads a spec written in JSON.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn from_json(source: &str) -> Result<Self, DialectError> {
+        serde_json::from_str(source).map_err(|err| DialectError::InvalidSpec(err.to_string()))
+    }
+}
+
+/// A trivial brainfuck substitution whose lexemes are only known at runtime (e.g.: read from a config file).
+///
+/// Lexemes are matched wherever they appear in the source, the longest one first, and everything else is
+/// ignored like comments in Brainfuck, comment markers can be set to also skip text holding lexemes. Whitespace
+/// inside of a lexeme matches any amount of whitespace, so `Ook. Ook.` also matches `Ook.` and `Ook.` on two
+/// different lines.
+///
+/// Matching inside of words means that with the lexemes `in` and `do`, the comment `window` reads as `,[`.
+/// Set `whole_words` to only match lexemes that aren't surrounded by letters, digits or underscores.
+///
+/// The dialect is used through the [`Dialect`] trait, which also makes it work with
+/// [`translate_with`](crate::token::translate_with) and [`Dialect::tokenize_reader`].
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let dialect = SubstitutionDialect::new(["a", "s", "l", "r", "p", "i", "(", ")"])
+///     .expect("Failed building dialect");
+///
+/// let tokens = dialect.to_tokens("aaa(rap ls)").expect("Failed parsing program");
+/// assert_eq!(tokens, Brainfuck::to_tokens("+++[>+.<-]").expect("Failed parsing program"));
+/// assert_eq!(dialect.from_tokens(&tokens), "aaa(rapls)");
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SubstitutionDialect {
+    lexemes: [String; 8],
+    /// Name of the language, only set for dialects built from a [`DialectSpec`]
+    pub name: Option<String>,
+    /// Whether lexemes only match text with the same case, `true` by default
+    pub case_sensitive: bool,
+    /// Whether lexemes only match when they aren't part of a longer word, `false` by default
+    pub whole_words: bool,
+    /// Starts a comment that runs until the end of the line
+    pub line_comment: Option<String>,
+    /// Start and end of a comment that can span several lines
+    pub block_comment: Option<(String, String)>,
+    /// Put between two lexemes by [`Dialect::from_tokens`], a space by default if any lexeme is longer than a
+    /// single character
+    pub separator: String,
+    /// Lines written by [`Dialect::from_tokens`] are wrapped before getting longer than this many characters
+    pub line_width: usize,
+}
+
+impl SubstitutionDialect {
+    /// Creates a dialect from the lexemes of `+`, `-`, `<`, `>`, `.`, `,`, `[` and `]`, in that order.
+    pub fn new<S: AsRef<str>>(lexemes: [S; 8]) -> Result<Self, DialectError> {
+        let lexemes = lexemes.map(|lexeme| {
+            lexeme
+                .as_ref()
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+        });
+
+        for (index, lexeme) in lexemes.iter().enumerate() {
+            if lexeme.is_empty() {
+                return Err(DialectError::EmptyLexeme(TOKEN_ORDER[index]));
+            }
+            if lexemes[..index].contains(lexeme) {
+                return Err(DialectError::DuplicateLexeme(lexeme.clone()));
+            }
+        }
+
+        let separator = if lexemes.iter().any(|lexeme| lexeme.chars().count() > 1) {
+            " "
+        } else {
+            ""
+        };
+        Ok(Self {
+            lexemes,
+            name: None,
+            case_sensitive: true,
+            whole_words: false,
+            line_comment: None,
+            block_comment: None,
+            separator: String::from(separator),
+            line_width: 80,
+        })
+    }
+
+    /// Creates the dialect described by `spec`.
+    ///
+    /// ```rust
+    /// use libbfi::prelude::*;
+    ///
+    /// let spec = DialectSpec {
+    ///     name: String::from("Upper"),
+    ///     lexemes: DialectLexemes {
+    ///         cell_add: String::from("UP"),
+    ///         cell_subtract: String::from("DOWN"),
+    ///         ptr_left: String::from("BACK"),
+    ///         ptr_right: String::from("NEXT"),
+    ///         print: String::from("SAY"),
+    ///         input: String::from("ASK"),
+    ///         jump_forwards: String::from("WHILE"),
+    ///         jump_backwards: String::from("END"),
+    ///     },
+    ///     separator: None,
+    ///     case_insensitive: true,
+    ///     whole_words: false,
+    ///     line_comment: Some(String::from("--")),
+    ///     block_comment: None,
+    /// };
+    /// let dialect = SubstitutionDialect::from_spec(spec).expect("Failed building dialect");
+    ///
+    /// let tokens = dialect.to_tokens("up Up -- say\nsay").expect("Failed parsing program");
+    /// assert_eq!(tokens, vec![BFToken::CellAdd, BFToken::CellAdd, BFToken::Print]);
+    /// assert_eq!(dialect.name.as_deref(), Some("Upper"));
+    /// ```
+    pub fn from_spec(spec: DialectSpec) -> Result<Self, DialectError> {
+        let lexemes = spec.lexemes;
+        let mut dialect = Self::new([
+            lexemes.cell_add,
+            lexemes.cell_subtract,
+            lexemes.ptr_left,
+            lexemes.ptr_right,
+            lexemes.print,
+            lexemes.input,
+            lexemes.jump_forwards,
+            lexemes.jump_backwards,
+        ])?;
+
+        if spec.case_insensitive {
+            for (index, lexeme) in dialect.lexemes.iter().enumerate() {
+                let lowercase = lexeme.to_lowercase();
+                if dialect.lexemes[..index]
+                    .iter()
+                    .any(|other| other.to_lowercase() == lowercase)
+                {
+                    return Err(DialectError::DuplicateLexeme(lexeme.clone()));
+                }
+            }
+        }
+        let comment_markers = spec.line_comment.iter().chain(
+            spec.block_comment
+                .iter()
+                .flat_map(|(start, end)| [start, end]),
+        );
+        for marker in comment_markers {
+            if marker.is_empty() {
+                return Err(DialectError::InvalidSpec(String::from(
+                    "comment markers can't be empty",
+                )));
+            }
+        }
+        // Comments are looked for before lexemes, so such a lexeme could never be matched
+        let comment_starts = spec
+            .line_comment
+            .iter()
+            .chain(spec.block_comment.iter().map(|(start, _)| start));
+        for marker in comment_starts {
+            if let Some(lexeme) = dialect
+                .lexemes
+                .iter()
+                .find(|lexeme| lexeme.starts_with(marker.as_str()))
+            {
+                return Err(DialectError::InvalidSpec(format!(
+                    "lexeme `{lexeme}` starts with the comment marker `{marker}`"
+                )));
+            }
+        }
+
+        dialect.name = Some(spec.name);
+        dialect.case_sensitive = !spec.case_insensitive;
+        dialect.whole_words = spec.whole_words;
+        dialect.line_comment = spec.line_comment;
+        dialect.block_comment = spec.block_comment;
+        if let Some(separator) = spec.separator {
+            dialect.separator = separator;
+        }
+        Ok(dialect)
+    }
+
+    /// Loads the dialect described by the spec file at `path`, read as TOML or JSON depending on its extension.
+    #[cfg(any(feature = "toml", feature = "json"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "toml", feature = "json"))))]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DialectError> {
+        let path = path.as_ref();
+        let parse: fn(&str) -> Result<DialectSpec, DialectError> =
+            match path.extension().and_then(|extension| extension.to_str()) {
+                #[cfg(feature = "toml")]
+                Some("toml") => DialectSpec::from_toml,
+                #[cfg(feature = "json")]
+                Some("json") => DialectSpec::from_json,
+                _ => {
+                    return Err(DialectError::InvalidSpec(format!(
+                        "unsupported spec format `{}`",
+                        path.display()
+                    )))
+                }
+            };
+        let source =
+            fs::read_to_string(path).map_err(|err| DialectError::Unreadable(err.to_string()))?;
+        let spec = parse(&source)?;
+        Self::from_spec(spec)
+    }
+
+    /// Reads every lexeme of `source`. Unless `complete`, also stops where the rest of `source` could still
+    /// turn into something else with more text (e.g.: `in` when `inc` is a lexeme), returning where.
+    fn scan(&self, source: &str, complete: bool) -> (Vec<SpannedToken>, Option<SourcePosition>) {
+        let mut tokens: Vec<SpannedToken> = Vec::new();
+        let mut next_offset = 0;
+        // Start of the word characters right before the current one that weren't part of a lexeme or comment
+        let mut word_start: Option<SourcePosition> = None;
+        // Whole words need the word before the stop to be read again, to know if a lexeme starts a word
+        let stop = |position: SourcePosition, word_start: Option<SourcePosition>| {
+            Some(word_start.filter(|_| self.whole_words).unwrap_or(position))
+        };
+
+        for (position, character) in positioned_chars(source) {
+            if position.offset < next_offset {
+                continue;
+            }
+            if !complete && self.is_unfinished(source, position.offset) {
+                return (tokens, stop(position, word_start));
+            }
+            let rest = &source[position.offset..];
+            if let Some(end) = self.comment_end(rest) {
+                next_offset = position.offset + end;
+                word_start = None;
+            } else if let Some((token, end)) = self.match_at(rest, word_start.is_some()) {
+                next_offset = position.offset + end;
+                word_start = None;
+                tokens.push(SpannedToken {
+                    token,
+                    start: position,
+                    end: next_offset,
+                });
+            } else if is_word_char(character) {
+                word_start.get_or_insert(position);
+            } else {
+                word_start = None;
+            }
+        }
+        match word_start {
+            Some(start) if !complete && self.whole_words => (tokens, Some(start)),
+            _ => (tokens, None),
+        }
+    }
+
+    /// Whether the text at `offset` runs into the end of `source` in the middle of a lexeme or comment.
+    fn is_unfinished(&self, source: &str, offset: usize) -> bool {
+        let rest = &source[offset..];
+        if let Some(marker) = &self.line_comment {
+            if rest.starts_with(marker.as_str()) && !rest.contains('\n') {
+                return true;
+            }
+        }
+        if let Some((start, end)) = &self.block_comment {
+            if let Some(body) = rest.strip_prefix(start.as_str()) {
+                if !body.contains(end.as_str()) {
+                    return true;
+                }
+            }
+        }
+        let mut markers = self.line_comment.iter().chain(
+            self.block_comment
+                .iter()
+                .flat_map(|(start, end)| [start, end]),
+        );
+        if markers.any(|marker| marker.len() > rest.len() && marker.starts_with(rest)) {
+            return true;
+        }
+        self.lexemes.iter().any(|lexeme| {
+            match match_lexeme(lexeme, rest, self.case_sensitive) {
+                // A word character coming next would break the match
+                Some(end) => {
+                    self.whole_words && end == rest.len() && lexeme.ends_with(is_word_char)
+                }
+                None => is_prefix(lexeme, rest, self.case_sensitive),
+            }
+        })
+    }
+
+    /// The token whose lexeme `text` starts with, alongside the length of the match. The longest lexeme wins.
+    ///
+    /// `after_word` tells if `text` comes right after word characters that weren't part of a lexeme or
+    /// comment.
+    fn match_at(&self, text: &str, after_word: bool) -> Option<(BFToken, usize)> {
+        self.lexemes
+            .iter()
+            .zip(TOKEN_ORDER)
+            .filter(|(lexeme, _)| {
+                !(self.whole_words && after_word && lexeme.starts_with(is_word_char))
+            })
+            .filter_map(|(lexeme, token)| {
+                let end = match_lexeme(lexeme, text, self.case_sensitive)?;
+                let before_word = text[end..].starts_with(is_word_char);
+                if self.whole_words && before_word && lexeme.ends_with(is_word_char) {
+                    return None;
+                }
+                Some((lexeme.len(), token, end))
+            })
+            .max_by_key(|(length, _, _)| *length)
+            .map(|(_, token, end)| (token, end))
+    }
+
+    /// Length of the comment `text` starts with, if it starts with one. Unterminated comments run until the end.
+    fn comment_end(&self, text: &str) -> Option<usize> {
+        if let Some(marker) = &self.line_comment {
+            if text.starts_with(marker.as_str()) {
+                return Some(text.find('\n').unwrap_or(text.len()));
+            }
+        }
+        if let Some((start, end)) = &self.block_comment {
+            if let Some(body) = text.strip_prefix(start.as_str()) {
+                let length = body
+                    .find(end.as_str())
+                    .map_or(text.len(), |offset| start.len() + offset + end.len());
+                return Some(length);
+            }
+        }
+        None
+    }
+}
+
+impl Dialect for SubstitutionDialect {
+    fn to_spanned_tokens(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        let (tokens, _) = self.scan(source, true);
+        check_brackets(source, &tokens)?;
+        Ok(tokens)
+    }
+    fn token_to_string(&self, element: BFToken) -> Result<String, TokenParseError> {
+        TOKEN_ORDER
+            .iter()
+            .position(|token| *token == element)
+            .map(|index| self.lexemes[index].clone())
+            .ok_or(TokenParseError::new(
+                ParseErrorKind::UnrepresentableToken,
+                None,
+                &format!("{element:?}"),
+            ))
+    }
+    fn separator(&self) -> &str {
+        &self.separator
+    }
+    fn line_width(&self) -> usize {
+        self.line_width
+    }
+    fn lex(
+        &self,
+        source: &str,
+    ) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+        Ok(self.scan(source, false))
+    }
+    fn lex_end(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        Ok(self.scan(source, true).0)
+    }
+}
+
+/// Length of the match if `text` starts with `lexeme`, where a space in `lexeme` matches any amount of whitespace.
+fn match_lexeme(lexeme: &str, text: &str, case_sensitive: bool) -> Option<usize> {
+    let mut rest = text;
+    for character in lexeme.chars() {
+        if character == ' ' {
+            let trimmed = rest.trim_start();
+            if trimmed.len() == rest.len() {
+                return None;
+            }
+            rest = trimmed;
+            continue;
+        }
+        let mut chars = rest.chars();
+        let found = chars.next()?;
+        let matches = found == character
+            || (!case_sensitive && found.to_lowercase().eq(character.to_lowercase()));
+        if !matches {
+            return None;
+        }
+        rest = chars.as_str();
+    }
+    Some(text.len() - rest.len())
+}
+
+/// Whether `text` runs out before matching all of `lexeme`, as [`match_lexeme`] would match it.
+fn is_prefix(lexeme: &str, text: &str, case_sensitive: bool) -> bool {
+    let mut rest = text;
+    for character in lexeme.chars() {
+        if rest.is_empty() {
+            return true;
+        }
+        if character == ' ' {
+            let trimmed = rest.trim_start();
+            if trimmed.len() == rest.len() {
+                return false;
+            }
+            rest = trimmed;
+            continue;
+        }
+        let mut chars = rest.chars();
+        let found = chars.next().unwrap_or_default();
+        let matches = found == character
+            || (!case_sensitive && found.to_lowercase().eq(character.to_lowercase()));
+        if !matches {
+            return false;
+        }
+        rest = chars.as_str();
+    }
+    false
+}
+
+fn is_word_char(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
@@ -0,0 +1,158 @@
+#![doc = r"Pieces shared by the binary formats of snapshots and traces"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{cell::Cell, token::BFToken};
+use std::{error::Error, fmt};
+
+/// Why binary data written by this crate could not be read back.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeError {
+    /// The data does not start with the expected magic bytes
+    InvalidMagic,
+    /// The data was written by a newer, unknown version of the format
+    UnsupportedVersion(u8),
+    /// The data was written with a different cell type
+    CellTypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    /// The data is truncated or describes an impossible state
+    Corrupted,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidMagic => write!(f, "data is not in the expected format"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            DecodeError::CellTypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "data has `{found}` cells but `{expected}` cells were expected"
+                )
+            }
+            DecodeError::Corrupted => write!(f, "data is corrupted"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Appends the header every format starts with: magic bytes, version and name of the cell type.
+pub(crate) fn write_header<C: Cell>(out: &mut Vec<u8>, magic: &[u8; 4], version: u8) {
+    out.extend_from_slice(magic);
+    out.push(version);
+    write_varint(out, C::NAME.len() as u64);
+    out.extend_from_slice(C::NAME.as_bytes());
+}
+
+/// Checks the header written by [`write_header`], returning the data following it.
+pub(crate) fn read_header<'a, C: Cell>(
+    bytes: &'a [u8],
+    magic: &[u8; 4],
+    version: u8,
+) -> Result<&'a [u8], DecodeError> {
+    let mut input = bytes
+        .strip_prefix(magic.as_slice())
+        .ok_or(DecodeError::InvalidMagic)?;
+
+    let (&found, rest) = input.split_first().ok_or(DecodeError::Corrupted)?;
+    if found != version {
+        return Err(DecodeError::UnsupportedVersion(found));
+    }
+    input = rest;
+
+    let name_len = read_len(&mut input)?;
+    let name = take(&mut input, name_len)?;
+    if name != C::NAME.as_bytes() {
+        return Err(DecodeError::CellTypeMismatch {
+            expected: C::NAME,
+            found: String::from_utf8_lossy(name).into_owned(),
+        });
+    }
+    Ok(input)
+}
+
+pub(crate) fn token_code(token: BFToken) -> u8 {
+    match token {
+        BFToken::CellAdd => 0,
+        BFToken::CellSubtract => 1,
+        BFToken::PtrLeft => 2,
+        BFToken::PtrRight => 3,
+        BFToken::Print => 4,
+        BFToken::Input => 5,
+        BFToken::JumpForwards => 6,
+        BFToken::JumpBackwards => 7,
+        BFToken::NoOP => 8,
+        BFToken::Debug => 9,
+    }
+}
+
+pub(crate) fn token_from_code(code: u8) -> Option<BFToken> {
+    Some(match code {
+        0 => BFToken::CellAdd,
+        1 => BFToken::CellSubtract,
+        2 => BFToken::PtrLeft,
+        3 => BFToken::PtrRight,
+        4 => BFToken::Print,
+        5 => BFToken::Input,
+        6 => BFToken::JumpForwards,
+        7 => BFToken::JumpBackwards,
+        8 => BFToken::NoOP,
+        9 => BFToken::Debug,
+        _ => return None,
+    })
+}
+
+/// Splits the next `len` bytes off of `input`.
+pub(crate) fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::Corrupted);
+    }
+    let (taken, rest) = input.split_at(len);
+    *input = rest;
+    Ok(taken)
+}
+
+/// Reads a varint that has to fit in a `usize`.
+pub(crate) fn read_len(input: &mut &[u8]) -> Result<usize, DecodeError> {
+    read_varint(input)
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or(DecodeError::Corrupted)
+}
+
+/// Appends `value` as a LEB128 varint.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a LEB128 varint written by [`write_varint`].
+pub(crate) fn read_varint(input: &mut &[u8]) -> Option<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Appends `value` as a zigzag-encoded varint, so small negative numbers stay small.
+pub(crate) fn write_signed_varint(out: &mut Vec<u8>, value: i64) {
+    write_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+/// Reads a varint written by [`write_signed_varint`].
+pub(crate) fn read_signed_varint(input: &mut &[u8]) -> Option<i64> {
+    let value = read_varint(input)?;
+    Some((value >> 1) as i64 ^ -((value & 1) as i64))
+}
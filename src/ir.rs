@@ -0,0 +1,193 @@
+#![doc = r"Intermediate representation that folds runs of brainfuck tokens into single instructions"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{runtime::RuntimeError, token::BFToken};
+
+/// A single IR instruction, see [`compile`] for how tokens map to it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction {
+    /// Adds `n` to the cell under the pointer (wrapping), negative values subtract
+    Add(i32),
+    /// Moves the pointer `n` cells, negative values move it to the left
+    Move(isize),
+    Print,
+    Input,
+    /// Jumps to the instruction at `target` (the matching `Jnz`) if the cell under the pointer is 0
+    Jz(usize),
+    /// Jumps to the instruction at `target` (the matching `Jz`) if the cell under the pointer is not 0
+    Jnz(usize),
+    /// Sets the cell under the pointer to 0, produced by [`optimize`] for `[-]` and `[+]`
+    Clear,
+    /// Moves the pointer `n` cells at a time until it lands on a 0, produced by [`optimize`] for `[>]` and `[<]`
+    Scan(isize),
+    /// Adds the cell under the pointer times `factor` to the cell `offset` cells away, produced by [`optimize`]
+    /// for move, copy and multiply loops such as `[->+<]`. Does nothing if the cell under the pointer is 0.
+    MulAdd {
+        offset: isize,
+        factor: i32,
+    },
+}
+
+/// Compiles a token stream into IR.
+///
+/// Runs of `CellAdd`/`CellSubtract` become a single `Add`, additions that cancel out are dropped entirely.
+/// Runs of `PtrLeft` or `PtrRight` become a single `Move`, but a change of direction starts a new one so that
+/// `<>` on the first cell still goes out of bounds. Brackets are resolved into absolute jump targets, unbalanced
+/// ones are reported with the index of the offending token.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let tokens = Brainfuck::to_tokens(String::from("+++[>>-<<-]")).expect("Failed parsing program");
+///
+/// assert_eq!(
+///     ir::compile(&tokens).expect("Failed compiling program"),
+///     vec![
+///         Instruction::Add(3),
+///         Instruction::Jz(6),
+///         Instruction::Move(2),
+///         Instruction::Add(-1),
+///         Instruction::Move(-2),
+///         Instruction::Add(-1),
+///         Instruction::Jnz(1),
+///     ]
+/// );
+/// ```
+pub fn compile(tokens: &[BFToken]) -> Result<Vec<Instruction>, RuntimeError> {
+    let mut program: Vec<Instruction> = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            BFToken::CellAdd | BFToken::CellSubtract => {
+                let amount = if *token == BFToken::CellAdd { 1 } else { -1 };
+                match program.last_mut() {
+                    Some(Instruction::Add(n)) => *n += amount,
+                    _ => program.push(Instruction::Add(amount)),
+                }
+                if program.last() == Some(&Instruction::Add(0)) {
+                    program.pop();
+                }
+            }
+            BFToken::PtrLeft | BFToken::PtrRight => {
+                // Only moves in the same direction are folded, so every cell the pointer passes through is
+                // still checked against the edges of the tape
+                let amount = if *token == BFToken::PtrRight { 1 } else { -1 };
+                match program.last_mut() {
+                    Some(Instruction::Move(n)) if n.signum() == amount => *n += amount,
+                    _ => program.push(Instruction::Move(amount)),
+                }
+            }
+            BFToken::Print => program.push(Instruction::Print),
+            BFToken::Input => program.push(Instruction::Input),
+            BFToken::JumpForwards => {
+                open.push((index, program.len()));
+                program.push(Instruction::Jz(0));
+            }
+            BFToken::JumpBackwards => {
+                let (_, start) = open
+                    .pop()
+                    .ok_or(RuntimeError::UnmatchedBracket { instruction: index })?;
+                program[start] = Instruction::Jz(program.len());
+                program.push(Instruction::Jnz(start));
+            }
+            BFToken::NoOP | BFToken::Debug => {}
+        }
+    }
+
+    match open.pop() {
+        Some((index, _)) => Err(RuntimeError::UnmatchedBracket { instruction: index }),
+        None => Ok(program),
+    }
+}
+
+/// Compiles a token stream into IR and replaces common loop idioms with single instructions.
+///
+/// * `[-]` and `[+]` become `Clear`
+/// * `[>]`, `[<<]`, ... become `Scan`
+/// * Loops that only add/subtract, decrement the starting cell by one and return to it (`[->+<]`,
+///   `[->+++>++<<]`, ...) become one `MulAdd` per touched cell followed by `Clear`
+///
+/// The result produces the same output as running the tokens directly.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let tokens = Brainfuck::to_tokens(String::from("+++[->++>+<<]")).expect("Failed parsing program");
+///
+/// assert_eq!(
+///     ir::optimize(&tokens).expect("Failed compiling program"),
+///     vec![
+///         Instruction::Add(3),
+///         Instruction::MulAdd { offset: 1, factor: 2 },
+///         Instruction::MulAdd { offset: 2, factor: 1 },
+///         Instruction::Clear,
+///     ]
+/// );
+/// ```
+pub fn optimize(tokens: &[BFToken]) -> Result<Vec<Instruction>, RuntimeError> {
+    let program = compile(tokens)?;
+    let mut optimized: Vec<Instruction> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    let mut index = 0;
+
+    while index < program.len() {
+        match program[index] {
+            Instruction::Jz(end) => match loop_idiom(&program[index + 1..end]) {
+                Some(replacement) => {
+                    optimized.extend(replacement);
+                    index = end;
+                }
+                None => {
+                    open.push(optimized.len());
+                    optimized.push(Instruction::Jz(0));
+                }
+            },
+            Instruction::Jnz(_) => {
+                let start = open.pop().expect("compile only produces balanced jumps");
+                optimized[start] = Instruction::Jz(optimized.len());
+                optimized.push(Instruction::Jnz(start));
+            }
+            other => optimized.push(other),
+        }
+        index += 1;
+    }
+
+    Ok(optimized)
+}
+
+/// Finds the replacement for a loop body, if it is one of the idioms handled by [`optimize`].
+fn loop_idiom(body: &[Instruction]) -> Option<Vec<Instruction>> {
+    match body {
+        [Instruction::Add(1 | -1)] => return Some(vec![Instruction::Clear]),
+        [Instruction::Move(n)] => return Some(vec![Instruction::Scan(*n)]),
+        _ => {}
+    }
+
+    let mut offset: isize = 0;
+    let mut deltas: Vec<(isize, i32)> = Vec::new();
+
+    for instruction in body {
+        match instruction {
+            Instruction::Move(n) => offset += n,
+            Instruction::Add(n) => match deltas.iter_mut().find(|(at, _)| *at == offset) {
+                Some((_, delta)) => *delta += n,
+                None => deltas.push((offset, *n)),
+            },
+            _ => return None,
+        }
+    }
+
+    if offset != 0 || !deltas.contains(&(0, -1)) {
+        return None;
+    }
+
+    deltas.sort_by_key(|(at, _)| *at);
+    let mut replacement: Vec<Instruction> = deltas
+        .into_iter()
+        .filter(|(at, factor)| *at != 0 && *factor != 0)
+        .map(|(offset, factor)| Instruction::MulAdd { offset, factor })
+        .collect();
+    replacement.push(Instruction::Clear);
+    Some(replacement)
+}
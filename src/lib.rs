@@ -1,7 +1,7 @@
 //! A library for interpreting and parsing Brainfuck code inspired by libbf.
 //!
 //! Includes support for regular Brainfuck and optionally any trivial-implementation or extension of it.
-//! Since any program interpreted by this library gets translated to tokens, translating one program to another shouldn't be an issue. Brainfuck -> Ook, Ook -> Blub, Blub -> Brainfuck, for example. And you can also run them interchangeably, by adding tokens in any of their states.
+//! Since any program interpreted by this library gets translated to tokens, translating one program to another shouldn't be an issue. Brainfuck -> Ook, Ook -> Blub, Blub -> Brainfuck, for example, with `translate::<Brainfuck, Ook>(source)`. And you can also run them interchangeably, by adding tokens in any of their states.
 //!
 //! ## Example Program
 //!
@@ -17,29 +17,58 @@
 //!
 //! std_brainfuck_app
 //!     .add_tokens(Brainfuck::to_tokens(program).expect("Failed parsing program"))
+//!     .expect("Failed loading program")
 //!     .run_full_stack(&mut stdin().lock(), &mut stdout())
+//!     .expect("Failed running program")
 //!     .clean_env();
 //!     
 //! std_brainfuck_app
 //!     .add_tokens(Ook::to_tokens(program_ook).expect("Failed parsing program"))
-//!     .run_full_stack(&mut stdin().lock(), &mut stdout());
+//!     .expect("Failed loading program")
+//!     .run_full_stack(&mut stdin().lock(), &mut stdout())
+//!     .expect("Failed running program");
 //! ```
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 pub mod builtin {
     pub mod bf_runtime;
+    pub mod ir_runtime;
     pub mod trivial_tokenizers;
 }
+pub mod cell;
+pub mod coverage;
+pub mod debugger;
+pub mod dialect;
+pub mod encoding;
+pub mod ir;
 pub mod matching;
+pub mod profile;
 pub mod runtime;
+pub mod session;
+pub mod snapshot;
+pub mod tape;
 pub mod token;
+pub mod trace;
 
 // Import this for necessary support to run the main Brainfuck interpreter
 pub mod prelude {
     pub use crate::builtin::bf_runtime::*;
+    pub use crate::builtin::ir_runtime::*;
     pub use crate::builtin::trivial_tokenizers::*;
     pub use crate::builtin::*;
+    pub use crate::cell::*;
+    pub use crate::coverage::*;
+    pub use crate::debugger::*;
+    pub use crate::dialect::*;
+    pub use crate::encoding::*;
+    pub use crate::ir;
+    pub use crate::ir::Instruction;
     pub use crate::matching;
+    pub use crate::profile::*;
     pub use crate::runtime::*;
+    pub use crate::session::*;
+    pub use crate::snapshot::*;
+    pub use crate::tape::*;
     pub use crate::token::*;
+    pub use crate::trace::*;
 }
@@ -84,3 +84,54 @@ pub fn find_matching_substring<T: Eq + Sized + Display>(
     }
     None
 }
+
+/// Pairs every `loop_start` in `full_list` with its `loop_end` in a single pass.
+///
+/// The returned table holds the index of the matching counterpart at the position of every loop element and
+/// `None` everywhere else. If the list is unbalanced the index of the offending element is returned instead.
+pub fn jump_table<T: Sized + PartialEq>(
+    full_list: &impl AsRef<[T]>,
+    loop_start: T,
+    loop_end: T,
+) -> Result<Vec<Option<usize>>, usize> {
+    let mut table = vec![None; full_list.as_ref().len()];
+    let mut open: Vec<usize> = Vec::new();
+
+    for (index, single_obj) in full_list.as_ref().iter().enumerate() {
+        if *single_obj == loop_start {
+            open.push(index);
+        } else if *single_obj == loop_end {
+            let start = open.pop().ok_or(index)?;
+            table[start] = Some(index);
+            table[index] = Some(start);
+        }
+    }
+
+    match open.pop() {
+        Some(index) => Err(index),
+        None => Ok(table),
+    }
+}
+
+/// Finds the innermost `loop_start` enclosing the element at `offset` (not counting `offset` itself).
+pub fn find_enclosing<T: Sized + PartialEq>(
+    full_list: &impl AsRef<[T]>,
+    loop_start: T,
+    loop_end: T,
+    offset: usize,
+) -> Option<usize> {
+    let mut balance = 0;
+
+    for index in (0..offset.min(full_list.as_ref().len())).rev() {
+        let mogu = &full_list.as_ref()[index];
+        if *mogu == loop_end {
+            balance += 1;
+        } else if *mogu == loop_start {
+            if balance == 0 {
+                return Some(index);
+            }
+            balance -= 1;
+        }
+    }
+    None
+}
@@ -0,0 +1,173 @@
+#![doc = r"Counting how often every instruction and loop runs"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{
+    matching::jump_table,
+    token::{BFToken, SpannedToken},
+};
+use std::{fmt::Write, ops::Range};
+
+/// Loops longer than this many characters are shortened in [`Profile::report`].
+const REPORT_LOOP_WIDTH: usize = 40;
+
+/// Execution counts collected by a runtime whose `profile` is set.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let source = "+++[>++[>+<-]<-]";
+/// let spanned = Brainfuck::to_spanned_tokens(source).expect("Failed parsing program");
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime.profile = Some(Profile::new());
+/// runtime
+///     .add_tokens(spanned.iter().map(|spanned| spanned.token).collect())
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+///     .expect("Failed running program");
+///
+/// let profile = runtime.profile.as_ref().unwrap();
+/// let loops = profile.loops(&runtime.instruction_stack);
+///
+/// // Outer loops include the steps of the loops they contain
+/// assert_eq!((loops[0].start, loops[0].end), (3, 15));
+/// assert_eq!((loops[1].start, loops[1].end), (7, 12));
+/// assert_eq!(loops[1].iterations, 6);
+/// println!("{}", profile.report(source, &spanned, 5));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Profile {
+    /// How many times each instruction ran, indexed like the instruction stack
+    pub counts: Vec<u64>,
+}
+
+/// Execution counts of a single loop, from its `[` to its `]`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LoopProfile {
+    pub start: usize,
+    pub end: usize,
+    /// How many times the loop was reached
+    pub entries: u64,
+    /// How many times the body ran to the closing bracket
+    pub iterations: u64,
+    /// Instructions executed inside of the loop, brackets and nested loops included
+    pub steps: u64,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of instructions executed.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Adds the counts of `other`, which must have been collected on the same program.
+    pub fn merge(&mut self, other: &Profile) -> &mut Self {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self
+    }
+
+    /// How many times the instruction at `instruction` ran.
+    pub fn count(&self, instruction: usize) -> u64 {
+        self.counts.get(instruction).copied().unwrap_or(0)
+    }
+
+    /// Every loop in `tokens`, the hottest first.
+    ///
+    /// `tokens` should be the instruction stack the profile was collected on, unbalanced tokens have no loops.
+    pub fn loops(&self, tokens: &[BFToken]) -> Vec<LoopProfile> {
+        let table =
+            jump_table(&tokens, BFToken::JumpForwards, BFToken::JumpBackwards).unwrap_or_default();
+        let mut loops: Vec<LoopProfile> = table
+            .into_iter()
+            .enumerate()
+            .filter_map(|(start, end)| end.filter(|end| *end > start).map(|end| (start, end)))
+            .map(|(start, end)| LoopProfile {
+                start,
+                end,
+                entries: self.count(start),
+                iterations: self.count(end),
+                steps: self
+                    .counts
+                    .get(start..=end)
+                    .map_or(0, |counts| counts.iter().sum()),
+            })
+            .collect();
+        loops.sort_by(|a, b| b.steps.cmp(&a.steps).then(a.start.cmp(&b.start)));
+        loops
+    }
+
+    /// Renders the `limit` hottest loops and instructions, quoting them from `source`.
+    ///
+    /// `tokens` are the spanned tokens of `source` as returned by
+    /// [`Tokenizer::to_spanned_tokens`](crate::token::Tokenizer::to_spanned_tokens), so every entry shows the
+    /// line and column it starts at and the exact text it was written as, comments included.
+    pub fn report(&self, source: &str, tokens: &[SpannedToken], limit: usize) -> String {
+        let mut report = format!("{} instructions executed\n", self.total());
+
+        let bare: Vec<BFToken> = tokens.iter().map(|spanned| spanned.token).collect();
+        report += "hot loops:\n";
+        for profile in self.loops(&bare).iter().take(limit) {
+            let (start, end) = (&tokens[profile.start], &tokens[profile.end]);
+            let _ = writeln!(
+                report,
+                "  {}:{}: {} steps, {} entries, {} iterations: {}",
+                start.start.line,
+                start.start.column,
+                profile.steps,
+                profile.entries,
+                profile.iterations,
+                quote(source, start.start.offset..end.end)
+            );
+        }
+
+        let mut instructions: Vec<(usize, u64)> = self
+            .counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        instructions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        report += "hot instructions:\n";
+        for (instruction, count) in instructions.into_iter().take(limit) {
+            let Some(token) = tokens.get(instruction) else {
+                continue;
+            };
+            let _ = writeln!(
+                report,
+                "  {}:{}: {count} times: {}",
+                token.start.line,
+                token.start.column,
+                quote(source, token.span())
+            );
+        }
+        report
+    }
+
+    pub(crate) fn record(&mut self, instruction: usize) {
+        if self.counts.len() <= instruction {
+            self.counts.resize(instruction + 1, 0);
+        }
+        self.counts[instruction] += 1;
+    }
+}
+
+/// The text of `source` in `span` on a single line, shortening long quotes.
+fn quote(source: &str, span: Range<usize>) -> String {
+    let text = source.get(span).unwrap_or_default();
+    let mut quoted = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((cut, _)) = quoted.char_indices().nth(REPORT_LOOP_WIDTH) {
+        quoted.truncate(cut);
+        quoted += "...";
+    }
+    quoted
+}
@@ -7,29 +7,386 @@
 //! runtime.memory = env.memory;
 //! runtime.run_full_stack().clean_env()
 
-use crate::token::BFToken;
-use std::{io::BufRead, io::Write};
+use crate::{
+    cell::{Arithmetic, Cell},
+    tape::Tape,
+    token::BFToken,
+};
+use std::{
+    error::Error,
+    fmt, io,
+    io::BufRead,
+    io::Write,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Everything that can go wrong while executing a program.
+///
+/// None of these are fatal to the host: the runtime is left at the failing instruction so it can be
+/// inspected, fixed up or cleaned with [`Runner::clean_env`].
+#[derive(Debug)]
+pub enum RuntimeError {
+    /// The instruction at `instruction` tried to move the pointer outside of the tape
+    PointerOutOfBounds { instruction: usize },
+    /// The bracket at `instruction` has no matching counterpart
+    UnmatchedBracket { instruction: usize },
+    /// The instruction at `instruction` pushed a cell past its limits under [`Arithmetic::Checked`]
+    CellOverflow { instruction: usize },
+    /// The reader or writer failed
+    Io(io::Error),
+    /// An input instruction was executed but the reader had nothing left
+    Eof,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::PointerOutOfBounds { instruction } => write!(
+                f,
+                "pointer moved out of bounds at instruction number {instruction}"
+            ),
+            RuntimeError::UnmatchedBracket { instruction } => write!(
+                f,
+                "matching bracket could not be found at instruction number {instruction}"
+            ),
+            RuntimeError::CellOverflow { instruction } => {
+                write!(f, "cell overflowed at instruction number {instruction}")
+            }
+            RuntimeError::Io(err) => write!(f, "I/O error: {err}"),
+            RuntimeError::Eof => write!(f, "reached end of input"),
+        }
+    }
+}
+
+impl Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RuntimeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        RuntimeError::Io(err)
+    }
+}
+
+/// What an input instruction does to the cell under the pointer once the reader has nothing left.
+///
+/// Brainfuck implementations never agreed on this, so pick the one the program was written against.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum EofPolicy {
+    /// Leave the cell as it was
+    Unchanged,
+    /// Set the cell to 0
+    Zero,
+    /// Set the cell to -1 (the maximum value for unsigned cells, 255 for 8-bit ones)
+    MaxValue,
+    /// Stop with [`RuntimeError::Eof`]
+    #[default]
+    Error,
+}
+
+/// Reads a single byte from `reader` into `cell`, following `policy` if there is nothing left to read.
+pub(crate) fn read_cell<C: Cell>(
+    reader: &mut impl BufRead,
+    cell: &mut C,
+    policy: EofPolicy,
+) -> Result<(), RuntimeError> {
+    let byte = loop {
+        match reader.fill_buf() {
+            Ok(buffer) => break buffer.first().copied(),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    match (byte, policy) {
+        (Some(byte), _) => {
+            reader.consume(1);
+            *cell = C::from_byte(byte);
+        }
+        (None, EofPolicy::Unchanged) => {}
+        (None, EofPolicy::Zero) => *cell = C::default(),
+        (None, EofPolicy::MaxValue) => {
+            *cell = C::default()
+                .add(-1, Arithmetic::Wrapping)
+                .expect("wrapping arithmetic never overflows")
+        }
+        (None, EofPolicy::Error) => return Err(RuntimeError::Eof),
+    }
+    Ok(())
+}
+
+/// What moving the pointer past either end of the tape does.
+///
+/// Reference interpreters disagree on this too, so programs written against one of them may rely on it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum PointerPolicy {
+    /// Continue from the other end of the tape
+    Wrap,
+    /// Stay on the last cell
+    Clamp,
+    /// Stop with [`RuntimeError::PointerOutOfBounds`]
+    Error,
+    /// Grow the tape, stopping with [`RuntimeError::PointerOutOfBounds`] if it can't grow (fixed tapes or
+    /// growable ones that hit their maximum size)
+    #[default]
+    Grow,
+}
+
+/// How a print instruction turns the cell under the pointer into output.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OutputMode {
+    /// Write the cell as a single raw byte, safe for binary output
+    #[default]
+    Byte,
+    /// Write the cell as a decimal number followed by a newline
+    Decimal,
+    /// Treat the cell as a Unicode code point and write it UTF-8 encoded (`0xE9` becomes "é", two bytes),
+    /// values that are not valid code points are written as U+FFFD
+    Utf8,
+}
+
+/// When the writer gets flushed, from the most eager to the laziest.
+///
+/// The `run_*` methods of [`Runner`] always flush once they stop, when stepping through a program with
+/// `next_instruction` anything not covered by the policy has to be flushed by the caller.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum FlushPolicy {
+    /// Flush after every print instruction
+    EveryByte,
+    /// Flush after printing a newline and before reading input, like a line-buffered terminal
+    #[default]
+    Newline,
+    /// Flush only before reading input, so prompts show up in interactive programs
+    BeforeInput,
+    /// Flush only once the run is over, the fastest option for batch runs
+    EndOfRun,
+}
+
+/// Writes `cell` to `writer` following `mode`, then flushes if `policy` asks for it.
+pub(crate) fn write_cell<C: Cell>(
+    writer: &mut impl Write,
+    cell: &C,
+    mode: OutputMode,
+    policy: FlushPolicy,
+) -> io::Result<()> {
+    match mode {
+        OutputMode::Byte => writer.write_all(&[cell.low_byte()])?,
+        OutputMode::Decimal => writeln!(writer, "{cell}")?,
+        OutputMode::Utf8 => {
+            let character = cell.code_point().and_then(char::from_u32);
+            write!(
+                writer,
+                "{}",
+                character.unwrap_or(char::REPLACEMENT_CHARACTER)
+            )?
+        }
+    }
+
+    let newline = mode == OutputMode::Decimal || cell.code_point() == Some(u32::from(b'\n'));
+    match policy {
+        FlushPolicy::EveryByte => writer.flush(),
+        FlushPolicy::Newline if newline => writer.flush(),
+        _ => Ok(()),
+    }
+}
+
+/// Flushes `writer` ahead of an input instruction if `policy` asks for it.
+pub(crate) fn flush_before_input(writer: &mut impl Write, policy: FlushPolicy) -> io::Result<()> {
+    match policy {
+        FlushPolicy::Newline | FlushPolicy::BeforeInput => writer.flush(),
+        _ => Ok(()),
+    }
+}
+
+/// Where and how much of the tape [`BFToken::Debug`] dumps, kept apart from the program's own output.
+///
+/// Each dump is a single line with the instruction index, the pointer and the `window` cells on each side of
+/// it, the cell under the pointer in brackets: `#5 ptr=1: 0:3 [1:2] 2:0`. Clones of a runtime share the same
+/// writer.
+#[derive(Clone)]
+pub struct DebugDump {
+    pub window: usize,
+    writer: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl DebugDump {
+    pub fn new(writer: impl Write + Send + 'static, window: usize) -> Self {
+        Self {
+            window,
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Dumps to the standard error stream.
+    pub fn stderr(window: usize) -> Self {
+        Self::new(io::stderr(), window)
+    }
+
+    pub(crate) fn dump<C: Cell>(
+        &self,
+        instruction: usize,
+        pointer: usize,
+        tape: &impl Tape<C>,
+    ) -> io::Result<()> {
+        let mut line = format!("#{instruction} ptr={pointer}:");
+        let end = pointer.saturating_add(self.window).saturating_add(1);
+        for index in pointer.saturating_sub(self.window)..end.min(tape.len()) {
+            if index == pointer {
+                line += &format!(" [{index}:{}]", tape[index]);
+            } else {
+                line += &format!(" {index}:{}", tape[index]);
+            }
+        }
+
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        writeln!(writer, "{line}")?;
+        writer.flush()
+    }
+}
+
+impl fmt::Debug for DebugDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugDump")
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Why a bounded run stopped. Unless it is `Finished`, running again picks up where it left off.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RunState {
+    /// Every instruction was executed
+    Finished,
+    /// The maximum number of steps was executed
+    StepLimit,
+    /// The deadline passed
+    Deadline,
+    /// The next instruction reads input but none is available yet, only returned by
+    /// [`Session`](crate::session::Session)
+    NeedsInput,
+    /// The last instruction produced output, only returned by [`Session`](crate::session::Session)
+    OutputReady,
+}
+
+/// How many steps are executed between checks of the clock in [`Runner::run_until`].
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
 
 pub trait Operator {
-    fn op_ptr_left(&mut self);
-    fn op_ptr_right(&mut self);
-    fn op_add_to_cell(&mut self);
-    fn op_sub_from_cell(&mut self);
-    fn op_print_cell_as_char(&self, writer: &mut impl Write);
-    fn op_input_to_cell(&mut self, reader: &mut impl BufRead);
-    fn op_jump_forwards(&mut self);
-    fn op_jump_backwards(&mut self);
+    fn op_ptr_left(&mut self) -> Result<(), RuntimeError>;
+    fn op_ptr_right(&mut self) -> Result<(), RuntimeError>;
+    fn op_add_to_cell(&mut self) -> Result<(), RuntimeError>;
+    fn op_sub_from_cell(&mut self) -> Result<(), RuntimeError>;
+    fn op_print_cell_as_char(&self, writer: &mut impl Write) -> Result<(), RuntimeError>;
+    fn op_input_to_cell(&mut self, reader: &mut impl BufRead) -> Result<(), RuntimeError>;
+    fn op_jump_forwards(&mut self) -> Result<(), RuntimeError>;
+    fn op_jump_backwards(&mut self) -> Result<(), RuntimeError>;
+    /// Handles [`BFToken::Debug`], which does nothing unless the runtime supports dumping its state.
+    fn op_debug_dump(&self) -> Result<(), RuntimeError> {
+        Ok(())
+    }
 }
 
 pub trait Runner: Clone + Sized {
     /// Adds a sequence of tokens (instructions) to the runtime's instruction stack.
-    fn add_tokens(&mut self, token_stream: Vec<BFToken>) -> &mut Self;
+    ///
+    /// Loops are validated here, so a stack with unbalanced brackets is rejected before it can run and the
+    /// instruction stack is left as it was.
+    fn add_tokens(&mut self, token_stream: Vec<BFToken>) -> Result<&mut Self, RuntimeError>;
     /// Resets the runtime environment to its initial state: instruction and pointer are set to zero,
     /// memory is cleared (all cells set to 0), and the instruction stack is emptied.
     fn clean_env(&mut self) -> &mut Self;
     /// Executes the next instruction from the instruction stack.
-    fn next_instruction(&mut self, reader: &mut impl BufRead, writer: &mut impl Write)
-        -> &mut Self;
-    /// Executes all instructions in the stack until the end is reached.
-    fn run_full_stack(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> &mut Self;
+    ///
+    /// On error the instruction counter is left pointing at the instruction that failed. The one exception is
+    /// a [`Tracer`](crate::trace::Tracer) failing with [`RuntimeError::Io`]: the instruction already ran, so
+    /// the counter points at the next one and running again doesn't repeat it.
+    fn next_instruction(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<&mut Self, RuntimeError>;
+    /// Executes all instructions in the stack until the end is reached or an instruction fails.
+    fn run_full_stack(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> Result<&mut Self, RuntimeError>;
+    /// Whether every instruction in the stack has been executed.
+    fn is_finished(&self) -> bool;
+    /// Whether the next instruction to be executed reads input.
+    fn wants_input(&self) -> bool;
+    /// Executes at most `max_steps` instructions, so programs like `+[]` can't run forever.
+    ///
+    /// ```rust
+    /// use libbfi::prelude::*;
+    ///
+    /// let mut runtime = BrainfuckRuntime::new();
+    /// runtime
+    ///     .add_tokens(Brainfuck::to_tokens(String::from("+[]")).expect("Failed parsing program"))
+    ///     .expect("Failed loading program");
+    ///
+    /// let state = runtime
+    ///     .run_with_limit(&mut std::io::empty(), &mut std::io::sink(), 1_000)
+    ///     .expect("Failed running program");
+    ///
+    /// assert_eq!(state, RunState::StepLimit);
+    /// ```
+    fn run_with_limit(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+        max_steps: usize,
+    ) -> Result<RunState, RuntimeError> {
+        run_bounded(self, reader, writer, Some(max_steps), None)
+    }
+    /// Executes instructions until the end is reached or `deadline` passes.
+    ///
+    /// The clock is only checked once every 1024 instructions, and an input instruction waiting on the
+    /// reader can't be interrupted, so the run may stop slightly after `deadline`.
+    fn run_until(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+        deadline: Instant,
+    ) -> Result<RunState, RuntimeError> {
+        run_bounded(self, reader, writer, None, Some(deadline))
+    }
+}
+
+fn run_bounded<R: Runner>(
+    runner: &mut R,
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    max_steps: Option<usize>,
+    deadline: Option<Instant>,
+) -> Result<RunState, RuntimeError> {
+    let mut steps: usize = 0;
+
+    let state = loop {
+        if runner.is_finished() {
+            break RunState::Finished;
+        }
+        if max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            break RunState::StepLimit;
+        }
+        if steps.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            break RunState::Deadline;
+        }
+        if let Err(err) = runner.next_instruction(reader, writer) {
+            writer.flush()?;
+            return Err(err);
+        }
+        steps += 1;
+    };
+
+    writer.flush()?;
+    Ok(state)
 }
@@ -0,0 +1,94 @@
+#![doc = r"Resumable execution for hosts that feed input and collect output incrementally"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::runtime::{RunState, Runner, RuntimeError};
+use std::collections::VecDeque;
+
+/// Runs a [`Runner`] without ever blocking on input.
+///
+/// Input is fed with [`Session::feed`] and output is buffered until [`Session::take_output`] is called.
+/// Instead of waiting for input, [`Session::resume`] returns [`RunState::NeedsInput`] so a host event loop can
+/// come back once more bytes arrived. Once [`Session::close_input`] is called, input instructions follow the
+/// runtime's `eof_policy` again.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from(",+.")).expect("Failed parsing program"))
+///     .expect("Failed loading program");
+///
+/// let mut session = Session::new(runtime);
+/// assert_eq!(session.resume().expect("Failed running program"), RunState::NeedsInput);
+///
+/// session.feed(b"a");
+/// assert_eq!(session.resume().expect("Failed running program"), RunState::OutputReady);
+/// assert_eq!(session.take_output(), b"b");
+/// assert_eq!(session.resume().expect("Failed running program"), RunState::Finished);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session<R: Runner> {
+    pub runtime: R,
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+    input_closed: bool,
+}
+
+impl<R: Runner> Session<R> {
+    pub fn new(runtime: R) -> Self {
+        Self {
+            runtime,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            input_closed: false,
+        }
+    }
+
+    /// Queues `bytes` to be read by upcoming input instructions.
+    pub fn feed(&mut self, bytes: &[u8]) -> &mut Self {
+        self.input.extend(bytes);
+        self
+    }
+
+    /// Marks the end of the input, once the queued bytes run out input instructions hit EOF.
+    pub fn close_input(&mut self) -> &mut Self {
+        self.input_closed = true;
+        self
+    }
+
+    /// Takes everything written by the program since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Runs until the program finishes, produces output or runs out of input.
+    pub fn resume(&mut self) -> Result<RunState, RuntimeError> {
+        self.resume_with_limit(usize::MAX)
+    }
+
+    /// Like [`Session::resume`], but also stops with [`RunState::StepLimit`] after `max_steps` instructions.
+    pub fn resume_with_limit(&mut self, max_steps: usize) -> Result<RunState, RuntimeError> {
+        for _ in 0..max_steps {
+            if self.runtime.is_finished() {
+                return Ok(RunState::Finished);
+            }
+            if self.runtime.wants_input() && self.input.is_empty() && !self.input_closed {
+                return Ok(RunState::NeedsInput);
+            }
+
+            let written = self.output.len();
+            self.runtime
+                .next_instruction(&mut self.input, &mut self.output)?;
+            if self.output.len() > written {
+                return Ok(RunState::OutputReady);
+            }
+        }
+
+        if self.runtime.is_finished() {
+            Ok(RunState::Finished)
+        } else {
+            Ok(RunState::StepLimit)
+        }
+    }
+}
@@ -0,0 +1,255 @@
+#![doc = r"Saving and restoring the full state of a runtime"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{
+    builtin::bf_runtime::BrainfuckRuntime,
+    cell::Cell,
+    encoding::{
+        read_header, read_len, take, token_code, token_from_code, write_header, write_varint,
+        DecodeError,
+    },
+    runtime::Runner,
+    tape::Tape,
+    token::BFToken,
+};
+use std::{error::Error, fmt};
+
+const MAGIC: &[u8; 4] = b"BFIS";
+const VERSION: u8 = 1;
+
+/// Largest tape [`Snapshot::from_bytes`] accepts, see [`Snapshot::from_bytes_with_limit`] to pick another one.
+pub const DEFAULT_MAX_SNAPSHOT_CELLS: usize = 1 << 24;
+
+/// Everything needed to continue a computation somewhere else: pointer, instruction counter, instruction
+/// stack and tape.
+///
+/// Runtime settings (policies, output mode, ...) are not part of a snapshot, the runtime being restored keeps
+/// its own. [`Snapshot::to_bytes`] produces a compact versioned binary format, with the `serde` feature the
+/// snapshot can also be serialized with any serde format such as JSON.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("+++>++.")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_with_limit(&mut std::io::empty(), &mut std::io::sink(), 4)
+///     .expect("Failed running program");
+///
+/// let bytes = runtime.snapshot().to_bytes();
+///
+/// let mut restored = BrainfuckRuntime::new();
+/// restored
+///     .restore(Snapshot::from_bytes(&bytes).expect("Failed reading snapshot"))
+///     .expect("Failed restoring snapshot");
+///
+/// assert_eq!(restored.pointer, 1);
+/// assert_eq!(restored.instruction, 4);
+/// assert_eq!(restored.memory, runtime.memory);
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot<C: Cell> {
+    pub pointer: usize,
+    pub instruction: usize,
+    pub instruction_stack: Vec<BFToken>,
+    /// Index of the starting cell, only different from 0 for tapes that grow to the left
+    pub origin: usize,
+    pub tape: Vec<C>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SnapshotError {
+    /// The data does not start like a snapshot
+    InvalidMagic,
+    /// The snapshot was written by a newer, unknown version of the format
+    UnsupportedVersion(u8),
+    /// The snapshot was taken from a runtime with a different cell type
+    CellTypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    /// The data is truncated or describes an impossible state
+    Corrupted,
+    /// The tape of the runtime being restored can't hold the snapshot's tape (e.g.: a fixed tape can't
+    /// restore cells left of the origin)
+    IncompatibleTape,
+    /// The snapshot's tape has more cells than the decoder was allowed to allocate
+    TapeTooLarge { cells: usize, limit: usize },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "data is not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            SnapshotError::CellTypeMismatch { expected, found } => write!(
+                f,
+                "snapshot has `{found}` cells but the runtime uses `{expected}` cells"
+            ),
+            SnapshotError::Corrupted => write!(f, "snapshot is corrupted"),
+            SnapshotError::IncompatibleTape => {
+                write!(f, "snapshot does not fit in the runtime's tape")
+            }
+            SnapshotError::TapeTooLarge { cells, limit } => {
+                write!(
+                    f,
+                    "snapshot tape has {cells} cells, more than the limit of {limit}"
+                )
+            }
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<DecodeError> for SnapshotError {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::InvalidMagic => SnapshotError::InvalidMagic,
+            DecodeError::UnsupportedVersion(version) => SnapshotError::UnsupportedVersion(version),
+            DecodeError::CellTypeMismatch { expected, found } => {
+                SnapshotError::CellTypeMismatch { expected, found }
+            }
+            DecodeError::Corrupted => SnapshotError::Corrupted,
+        }
+    }
+}
+
+impl<C: Cell> Snapshot<C> {
+    /// Encodes the snapshot in the binary format.
+    ///
+    /// Runs of empty cells are stored as a single count, so mostly empty tapes stay small.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        write_header::<C>(&mut out, MAGIC, VERSION);
+        write_varint(&mut out, self.pointer as u64);
+        write_varint(&mut out, self.instruction as u64);
+        write_varint(&mut out, self.origin as u64);
+
+        write_varint(&mut out, self.instruction_stack.len() as u64);
+        out.extend(
+            self.instruction_stack
+                .iter()
+                .map(|token| token_code(*token)),
+        );
+
+        write_varint(&mut out, self.tape.len() as u64);
+        let mut cells = self.tape.as_slice();
+        while !cells.is_empty() {
+            let empty = cells.iter().take_while(|cell| cell.is_zero()).count();
+            let filled = cells[empty..]
+                .iter()
+                .take_while(|cell| !cell.is_zero())
+                .count();
+            write_varint(&mut out, empty as u64);
+            write_varint(&mut out, filled as u64);
+            cells[empty..empty + filled]
+                .iter()
+                .for_each(|cell| cell.encode(&mut out));
+            cells = &cells[empty + filled..];
+        }
+
+        out
+    }
+
+    /// Decodes a snapshot written by [`Snapshot::to_bytes`].
+    ///
+    /// The data is treated as untrusted, so the tape is limited to [`DEFAULT_MAX_SNAPSHOT_CELLS`] cells.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        Self::from_bytes_with_limit(bytes, DEFAULT_MAX_SNAPSHOT_CELLS)
+    }
+
+    /// Decodes a snapshot, refusing tapes longer than `max_cells` before allocating them.
+    pub fn from_bytes_with_limit(bytes: &[u8], max_cells: usize) -> Result<Self, SnapshotError> {
+        let mut input = read_header::<C>(bytes, MAGIC, VERSION)?;
+
+        let pointer = read_len(&mut input)?;
+        let instruction = read_len(&mut input)?;
+        let origin = read_len(&mut input)?;
+
+        let stack_len = read_len(&mut input)?;
+        let instruction_stack = take(&mut input, stack_len)?
+            .iter()
+            .map(|code| token_from_code(*code))
+            .collect::<Option<Vec<BFToken>>>()
+            .ok_or(SnapshotError::Corrupted)?;
+
+        let tape_len = read_len(&mut input)?;
+        if tape_len > max_cells {
+            return Err(SnapshotError::TapeTooLarge {
+                cells: tape_len,
+                limit: max_cells,
+            });
+        }
+        let mut tape: Vec<C> = Vec::new();
+        while tape.len() < tape_len {
+            let empty = read_len(&mut input)?;
+            let filled = read_len(&mut input)?;
+            let run_end = tape
+                .len()
+                .checked_add(empty)
+                .and_then(|len| len.checked_add(filled));
+            if run_end.is_none_or(|end| end > tape_len) {
+                return Err(SnapshotError::Corrupted);
+            }
+            tape.resize(tape.len() + empty, C::default());
+            for _ in 0..filled {
+                tape.push(C::decode(&mut input).ok_or(SnapshotError::Corrupted)?);
+            }
+        }
+
+        if !input.is_empty() {
+            return Err(SnapshotError::Corrupted);
+        }
+
+        Ok(Self {
+            pointer,
+            instruction,
+            instruction_stack,
+            origin,
+            tape,
+        })
+    }
+}
+
+impl<C: Cell, T: Tape<C>> BrainfuckRuntime<C, T> {
+    /// Captures the current state of the runtime.
+    pub fn snapshot(&self) -> Snapshot<C> {
+        Snapshot {
+            pointer: self.pointer,
+            instruction: self.instruction,
+            instruction_stack: self.instruction_stack.clone(),
+            origin: self.memory.origin(),
+            tape: (0..self.memory.len())
+                .map(|index| self.memory[index].clone())
+                .collect(),
+        }
+    }
+
+    /// Replaces the state of the runtime with `snapshot`. On error the runtime is left untouched.
+    pub fn restore(&mut self, snapshot: Snapshot<C>) -> Result<&mut Self, SnapshotError> {
+        if snapshot.pointer >= snapshot.tape.len()
+            || snapshot.instruction > snapshot.instruction_stack.len()
+        {
+            return Err(SnapshotError::Corrupted);
+        }
+
+        let mut restored = self.clone();
+        restored.clean_env();
+        restored
+            .add_tokens(snapshot.instruction_stack)
+            .map_err(|_| SnapshotError::Corrupted)?;
+        if !restored.memory.replace(snapshot.tape, snapshot.origin) {
+            return Err(SnapshotError::IncompatibleTape);
+        }
+        restored.pointer = snapshot.pointer;
+        restored.instruction = snapshot.instruction;
+
+        *self = restored;
+        Ok(self)
+    }
+}
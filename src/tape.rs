@@ -0,0 +1,166 @@
+#![doc = r"Memory layouts (tapes) that can back a runtime"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::cell::Cell;
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    ops::{Index, IndexMut},
+};
+
+/// The memory of a runtime, indexed by the runtime's pointer.
+///
+/// A plain `Vec` is a fixed tape that never grows, [`GrowableTape`] grows on demand in both directions.
+pub trait Tape<C: Cell>: Clone + Debug + Index<usize, Output = C> + IndexMut<usize> {
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Adds an empty cell before index 0, shifting every existing cell one index to the right.
+    /// Returns `false` if the tape can't grow.
+    fn grow_left(&mut self) -> bool;
+    /// Adds an empty cell after the last index. Returns `false` if the tape can't grow.
+    fn grow_right(&mut self) -> bool;
+    /// Brings the tape back to its initial state, with every cell set to 0.
+    fn clear(&mut self);
+    /// Index of the starting cell (position 0), only different from 0 once the tape grew to the left.
+    fn origin(&self) -> usize {
+        0
+    }
+    /// Replaces every cell of the tape, returns `false` if the tape can't hold `cells` with the starting cell
+    /// at `origin`.
+    fn replace(&mut self, cells: Vec<C>, origin: usize) -> bool;
+}
+
+impl<C: Cell> Tape<C> for Vec<C> {
+    fn len(&self) -> usize {
+        self.len()
+    }
+    fn grow_left(&mut self) -> bool {
+        false
+    }
+    fn grow_right(&mut self) -> bool {
+        false
+    }
+    fn clear(&mut self) {
+        self.fill(C::default());
+    }
+    fn replace(&mut self, cells: Vec<C>, origin: usize) -> bool {
+        if origin != 0 {
+            return false;
+        }
+        *self = cells;
+        true
+    }
+}
+
+/// A tape that starts with a single cell and grows in both directions as the pointer reaches its ends.
+///
+/// Cells left of the starting one have negative positions, use [`GrowableTape::get`] to read them by position
+/// instead of by index. With `max_size` set, growing past that many cells is refused.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u8>::new());
+///
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("<<+++>>>+")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+///     .expect("Failed running program");
+///
+/// assert_eq!(runtime.memory.len(), 4);
+/// assert_eq!(runtime.memory.get(-2), Some(&3));
+/// assert_eq!(runtime.memory.get(1), Some(&1));
+/// ```
+#[derive(Debug, Clone)]
+pub struct GrowableTape<C: Cell = u8> {
+    cells: VecDeque<C>,
+    origin: usize,
+    pub max_size: Option<usize>,
+}
+
+impl<C: Cell> GrowableTape<C> {
+    /// Creates a tape with no size limit.
+    pub fn new() -> Self {
+        Self {
+            cells: VecDeque::from([C::default()]),
+            origin: 0,
+            max_size: None,
+        }
+    }
+
+    /// Creates a tape that never grows past `max_size` cells.
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self {
+            max_size: Some(max_size),
+            ..Self::new()
+        }
+    }
+
+    /// The cell at `position` relative to the starting cell, if the tape has grown that far.
+    pub fn get(&self, position: isize) -> Option<&C> {
+        self.cells.get(self.origin.checked_add_signed(position)?)
+    }
+
+    fn can_grow(&self) -> bool {
+        self.max_size.is_none_or(|max| self.cells.len() < max)
+    }
+}
+
+impl<C: Cell> Default for GrowableTape<C> {
+    fn default() -> Self {
+        GrowableTape::new()
+    }
+}
+
+impl<C: Cell> Index<usize> for GrowableTape<C> {
+    type Output = C;
+
+    fn index(&self, index: usize) -> &C {
+        &self.cells[index]
+    }
+}
+
+impl<C: Cell> IndexMut<usize> for GrowableTape<C> {
+    fn index_mut(&mut self, index: usize) -> &mut C {
+        &mut self.cells[index]
+    }
+}
+
+impl<C: Cell> Tape<C> for GrowableTape<C> {
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+    fn grow_left(&mut self) -> bool {
+        if !self.can_grow() {
+            return false;
+        }
+        self.cells.push_front(C::default());
+        self.origin += 1;
+        true
+    }
+    fn grow_right(&mut self) -> bool {
+        if !self.can_grow() {
+            return false;
+        }
+        self.cells.push_back(C::default());
+        true
+    }
+    fn clear(&mut self) {
+        self.cells = VecDeque::from([C::default()]);
+        self.origin = 0;
+    }
+    fn origin(&self) -> usize {
+        self.origin
+    }
+    fn replace(&mut self, cells: Vec<C>, origin: usize) -> bool {
+        if origin >= cells.len() || self.max_size.is_some_and(|max| cells.len() > max) {
+            return false;
+        }
+        self.cells = VecDeque::from(cells);
+        self.origin = origin;
+        true
+    }
+}
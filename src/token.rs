@@ -1,7 +1,17 @@
 #![doc = "Definition for generic brainfuck token + everything necessary to write tokenizers"]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    io::{BufRead, ErrorKind},
+    marker::PhantomData,
+    ops::Range,
+};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BFToken {
     CellAdd,
     CellSubtract,
@@ -12,22 +22,700 @@ pub enum BFToken {
     JumpForwards,
     JumpBackwards,
     NoOP, // Only for internal use
+    /// Dumps the tape around the pointer for debugging, only produced by tokenizers that opt into it
+    Debug,
+}
+
+/// Location of a character inside of a program's source text.
+///
+/// `offset` is the byte offset, `line` and `column` start at 1 and columns are counted in characters.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SourcePosition {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for SourcePosition {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Why a program (or token) could not be translated.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ParseErrorKind {
+    /// The program ends in the middle of a multi-character instruction (e.g.: a lone `Ook.`)
+    DanglingHalfToken,
+    /// A complete lexeme was read but it does not map to any instruction
+    UnknownWord,
+    /// A loop bracket has no matching counterpart
+    UnbalancedBracket,
+    /// The token has no textual representation in the target language
+    UnrepresentableToken,
+    /// The program could not be read, `lexeme` holds the reason
+    Unreadable,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseErrorKind::DanglingHalfToken => "dangling half-token",
+            ParseErrorKind::UnknownWord => "unknown word",
+            ParseErrorKind::UnbalancedBracket => "unbalanced bracket",
+            ParseErrorKind::UnrepresentableToken => "unrepresentable token",
+            ParseErrorKind::Unreadable => "unreadable program",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TokenParseError {
+    pub kind: ParseErrorKind,
+    /// Where the offending lexeme starts, `None` when the error does not come from source text
+    pub position: Option<SourcePosition>,
+    pub lexeme: String,
+}
+
+impl TokenParseError {
+    pub fn new(kind: ParseErrorKind, position: Option<SourcePosition>, lexeme: &str) -> Self {
+        Self {
+            kind,
+            position,
+            lexeme: String::from(lexeme),
+        }
+    }
+}
+
+impl fmt::Display for TokenParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} `{}`", self.kind, self.lexeme)?;
+        if let Some(position) = self.position {
+            write!(f, " at {position} (byte {})", position.offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TokenParseError {}
+
+/// A token alongside the part of the source text it was read from.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let source = String::from("Ook. Ook.\nOok! Ook.");
+/// let tokens = Ook::to_spanned_tokens(&source).expect("Failed parsing program");
+///
+/// assert_eq!(tokens[1].token, BFToken::Print);
+/// assert_eq!(&source[tokens[1].span()], "Ook! Ook.");
+/// assert_eq!((tokens[1].start.line, tokens[1].start.column), (2, 1));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SpannedToken {
+    pub token: BFToken,
+    /// Where the lexeme starts
+    pub start: SourcePosition,
+    /// Byte offset right after the lexeme
+    pub end: usize,
+}
+
+impl SpannedToken {
+    /// Byte range of the lexeme in the source text.
+    pub fn span(&self) -> Range<usize> {
+        self.start.offset..self.end
+    }
 }
 
-#[derive(Debug)]
-pub struct TokenParseError;
 pub trait Tokenizer {
-    fn to_tokens(element: String) -> Result<Vec<BFToken>, TokenParseError>;
+    /// Put between two lexemes by [`Tokenizer::from_tokens`]
+    const SEPARATOR: &'static str = "";
+    /// Lines written by [`Tokenizer::from_tokens`] are wrapped before getting longer than this many characters
+    const LINE_WIDTH: usize = 80;
+
+    /// Translates `source` into tokens, keeping track of where each of them comes from.
+    fn to_spanned_tokens(source: &str) -> Result<Vec<SpannedToken>, TokenParseError>;
     fn token_to_string(element: BFToken) -> Result<String, TokenParseError>;
+
+    /// Reads every complete lexeme of `source` without checking that brackets are balanced.
+    ///
+    /// Also returns where the unfinished lexeme at the end of `source` starts, if there is one (e.g.: a lone
+    /// `Ook.`), so it can be completed by text that wasn't read yet. Used by [`Tokenizer::tokenize_reader`].
+    ///
+    /// The default treats all of `source` as unfinished until [`Tokenizer::to_spanned_tokens`] accepts it, so
+    /// a reader only yields tokens once the text read so far is a whole program. Tokenizers that can tell
+    /// where their lexemes end should override it.
+    fn lex(source: &str) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+        match Self::to_spanned_tokens(source) {
+            Ok(tokens) => Ok((tokens, None)),
+            Err(_) => Ok((Vec::new(), Some(SourcePosition::default()))),
+        }
+    }
+    fn to_tokens(source: impl AsRef<str>) -> Result<Vec<BFToken>, TokenParseError> {
+        Ok(Self::to_spanned_tokens(source.as_ref())?
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect())
+    }
+    /// Writes a whole program, wrapping lines at [`Tokenizer::LINE_WIDTH`].
+    ///
+    /// Tokens without a representation in this language are left out, see [`translate`] to catch them instead.
+    fn from_tokens(tokens: &[BFToken]) -> String {
+        StaticDialect::<Self>::new().from_tokens(tokens)
+    }
+    /// Translates the program read from `reader` one buffer at a time.
+    ///
+    /// Only the text of a lexeme that is cut off at the end of a buffer is kept between reads, so long
+    /// programs don't have to be loaded at once as long as [`Tokenizer::lex`] can tell where lexemes end.
+    ///
+    /// ```rust
+    /// use libbfi::prelude::*;
+    ///
+    /// let tokens: Result<Vec<BFToken>, TokenParseError> =
+    ///     Ook::tokenize_reader("Ook. Ook. Ook!\nOok? Ook! Ook! Ook? Ook!".as_bytes()).collect();
+    ///
+    /// assert_eq!(
+    ///     tokens,
+    ///     Ok(vec![BFToken::CellAdd, BFToken::JumpForwards, BFToken::CellSubtract, BFToken::JumpBackwards])
+    /// );
+    /// ```
+    fn tokenize_reader<R: BufRead>(reader: R) -> TokenReader<StaticDialect<Self>, R>
+    where
+        Self: Sized,
+    {
+        TokenReader::new(StaticDialect::new(), reader)
+    }
+}
+
+/// A tokenizer that is a value instead of a type, for languages that are only known at runtime like a
+/// [`SubstitutionDialect`](crate::dialect::SubstitutionDialect).
+///
+/// Every [`Tokenizer`] can be used where a dialect is expected through [`StaticDialect`].
+pub trait Dialect {
+    /// Translates `source` into tokens, keeping track of where each of them comes from.
+    fn to_spanned_tokens(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError>;
+    fn token_to_string(&self, element: BFToken) -> Result<String, TokenParseError>;
+
+    /// Put between two lexemes by [`Dialect::from_tokens`]
+    fn separator(&self) -> &str {
+        ""
+    }
+    /// Lines written by [`Dialect::from_tokens`] are wrapped before getting longer than this many characters
+    fn line_width(&self) -> usize {
+        80
+    }
+    /// Same as [`Tokenizer::lex`], used by [`Dialect::tokenize_reader`].
+    fn lex(
+        &self,
+        source: &str,
+    ) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+        match self.to_spanned_tokens(source) {
+            Ok(tokens) => Ok((tokens, None)),
+            Err(_) => Ok((Vec::new(), Some(SourcePosition::default()))),
+        }
+    }
+    /// Tokenizes what [`Dialect::lex`] left dangling once the program ended, used by
+    /// [`Dialect::tokenize_reader`]. Brackets are checked by the reader, as they can match ones read before.
+    ///
+    /// Defaults to [`Dialect::to_spanned_tokens`], which suits dialects that only leave unfinished lexemes
+    /// dangling.
+    fn lex_end(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        self.to_spanned_tokens(source)
+    }
+    fn to_tokens(&self, source: impl AsRef<str>) -> Result<Vec<BFToken>, TokenParseError>
+    where
+        Self: Sized,
+    {
+        Ok(self
+            .to_spanned_tokens(source.as_ref())?
+            .into_iter()
+            .map(|spanned| spanned.token)
+            .collect())
+    }
+    /// Writes a whole program, wrapping lines at [`Dialect::line_width`].
+    ///
+    /// Tokens without a representation in this language are left out, see [`translate_with`] to catch them
+    /// instead.
+    // Named after `Tokenizer::from_tokens`, which it mirrors
+    #[allow(clippy::wrong_self_convention)]
+    fn from_tokens(&self, tokens: &[BFToken]) -> String {
+        join_lexemes(
+            tokens
+                .iter()
+                .filter_map(|token| self.token_to_string(*token).ok()),
+            self.separator(),
+            self.line_width(),
+        )
+    }
+    /// Same as [`Tokenizer::tokenize_reader`].
+    fn tokenize_reader<R: BufRead>(&self, reader: R) -> TokenReader<&Self, R>
+    where
+        Self: Sized,
+    {
+        TokenReader::new(self, reader)
+    }
+}
+
+impl<D: Dialect + ?Sized> Dialect for &D {
+    fn to_spanned_tokens(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        (**self).to_spanned_tokens(source)
+    }
+    fn token_to_string(&self, element: BFToken) -> Result<String, TokenParseError> {
+        (**self).token_to_string(element)
+    }
+    fn separator(&self) -> &str {
+        (**self).separator()
+    }
+    fn line_width(&self) -> usize {
+        (**self).line_width()
+    }
+    fn lex(
+        &self,
+        source: &str,
+    ) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+        (**self).lex(source)
+    }
+    fn lex_end(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        (**self).lex_end(source)
+    }
+}
+
+/// The [`Dialect`] of the [`Tokenizer`] `T`.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let tokens = StaticDialect::<Ook>::new().to_tokens("Ook. Ook. Ook! Ook.");
+/// assert_eq!(tokens, Ook::to_tokens("Ook. Ook. Ook! Ook."));
+/// ```
+pub struct StaticDialect<T: Tokenizer + ?Sized>(PhantomData<T>);
+
+impl<T: Tokenizer + ?Sized> StaticDialect<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Tokenizer + ?Sized> Default for StaticDialect<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Tokenizer + ?Sized> Clone for StaticDialect<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Tokenizer + ?Sized> Copy for StaticDialect<T> {}
+
+impl<T: Tokenizer + ?Sized> fmt::Debug for StaticDialect<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StaticDialect<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T: Tokenizer + ?Sized> Dialect for StaticDialect<T> {
+    fn to_spanned_tokens(&self, source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+        T::to_spanned_tokens(source)
+    }
+    fn token_to_string(&self, element: BFToken) -> Result<String, TokenParseError> {
+        T::token_to_string(element)
+    }
+    fn separator(&self) -> &str {
+        T::SEPARATOR
+    }
+    fn line_width(&self) -> usize {
+        T::LINE_WIDTH
+    }
+    fn lex(
+        &self,
+        source: &str,
+    ) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+        T::lex(source)
+    }
+}
+
+/// Translates a whole program from the language of `F` to the language of `T`.
+///
+/// Fails if `source` can't be parsed or holds a token that `T` can't represent (e.g.: a
+/// [`BFToken::Debug`] going to a language without it).
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let ook = translate::<Brainfuck, Ook>("+[-]").expect("Failed translating program");
+/// assert_eq!(ook, "Ook. Ook. Ook! Ook? Ook! Ook! Ook? Ook!");
+///
+/// let brainfuck = translate::<Ook, Brainfuck>(&ook).expect("Failed translating program");
+/// assert_eq!(brainfuck, "+[-]");
+/// ```
+pub fn translate<F: Tokenizer, T: Tokenizer>(source: &str) -> Result<String, TokenParseError> {
+    translate_with(
+        source,
+        &StaticDialect::<F>::new(),
+        &StaticDialect::<T>::new(),
+    )
+}
+
+/// Translates a whole program from the dialect `from` to the dialect `to`, like [`translate`].
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let letters = SubstitutionDialect::new(["a", "s", "l", "r", "p", "i", "(", ")"])
+///     .expect("Failed building dialect");
+///
+/// let brainfuck = translate_with("a(s)", &letters, &StaticDialect::<Brainfuck>::new())
+///     .expect("Failed translating program");
+/// assert_eq!(brainfuck, "+[-]");
+/// ```
+pub fn translate_with(
+    source: &str,
+    from: &impl Dialect,
+    to: &impl Dialect,
+) -> Result<String, TokenParseError> {
+    let tokens = from.to_tokens(source)?;
+    for token in tokens.iter().filter(|token| **token != BFToken::NoOP) {
+        to.token_to_string(*token)?;
+    }
+    Ok(to.from_tokens(&tokens))
+}
+
+/// Iterator over the tokens of a program read from a [`BufRead`], returned by [`Tokenizer::tokenize_reader`]
+/// and [`Dialect::tokenize_reader`].
+///
+/// Brackets are checked as the program is read: a closing bracket without an opening one fails as soon as it
+/// is reached, while unclosed brackets are reported once the whole program was read. The iterator stops after
+/// the first error.
+#[derive(Debug)]
+pub struct TokenReader<D: Dialect, R: BufRead> {
+    dialect: D,
+    reader: R,
+    /// Text that was read but not tokenized yet, because it ends in an unfinished lexeme
+    pending: String,
+    /// Bytes of a character that is cut off at the end of the last buffer
+    undecoded: Vec<u8>,
+    /// Position of the start of `pending` in the whole program
+    start: SourcePosition,
+    tokens: VecDeque<SpannedToken>,
+    /// Opening brackets that weren't closed yet, alongside their lexeme
+    open: Vec<(SourcePosition, String)>,
+    /// Error to report once the tokens read before it were yielded
+    error: Option<TokenParseError>,
+    done: bool,
+}
+
+impl<D: Dialect, R: BufRead> TokenReader<D, R> {
+    fn new(dialect: D, reader: R) -> Self {
+        Self {
+            dialect,
+            reader,
+            pending: String::new(),
+            undecoded: Vec::new(),
+            start: SourcePosition::default(),
+            tokens: VecDeque::new(),
+            open: Vec::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Reads buffers until some tokens are available or the program ends.
+    fn fill(&mut self) -> Result<(), TokenParseError> {
+        while self.tokens.is_empty() {
+            let read = self.read_chunk()?;
+            let (tokens, dangling) = self
+                .dialect
+                .lex(&self.pending)
+                .map_err(|err| self.shift_error(err))?;
+            self.push_tokens(tokens)?;
+
+            let consumed = dangling.map_or(self.pending.len(), |position| position.offset);
+            self.start = advance(self.start, &self.pending[..consumed]);
+            self.pending.drain(..consumed);
+
+            if read == 0 {
+                // Nothing can complete the rest anymore, so it has to stand on its own
+                if !self.pending.is_empty() {
+                    let tokens = self
+                        .dialect
+                        .lex_end(&self.pending)
+                        .map_err(|err| self.shift_error(err))?;
+                    self.push_tokens(tokens)?;
+                }
+                if let Some((position, lexeme)) = self.open.pop() {
+                    return Err(TokenParseError::new(
+                        ParseErrorKind::UnbalancedBracket,
+                        Some(position),
+                        &lexeme,
+                    ));
+                }
+                self.done = true;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues `tokens` lexed from `pending`, checking their brackets.
+    fn push_tokens(&mut self, tokens: Vec<SpannedToken>) -> Result<(), TokenParseError> {
+        for mut spanned in tokens {
+            spanned.end += self.start.offset;
+            spanned.start = shift(spanned.start, self.start);
+            match spanned.token {
+                BFToken::JumpForwards => self
+                    .open
+                    .push((spanned.start, self.lexeme(&spanned).to_string())),
+                BFToken::JumpBackwards if self.open.pop().is_none() => {
+                    return Err(TokenParseError::new(
+                        ParseErrorKind::UnbalancedBracket,
+                        Some(spanned.start),
+                        self.lexeme(&spanned),
+                    ));
+                }
+                _ => {}
+            }
+            self.tokens.push_back(spanned);
+        }
+        Ok(())
+    }
+
+    /// Appends the next buffer of `reader` to `pending`, returning how many bytes were read.
+    fn read_chunk(&mut self) -> Result<usize, TokenParseError> {
+        let unreadable =
+            |message: &str| TokenParseError::new(ParseErrorKind::Unreadable, None, message);
+
+        let chunk = loop {
+            match self.reader.fill_buf() {
+                Ok(chunk) => break chunk,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(unreadable(&err.to_string())),
+            }
+        };
+        let read = chunk.len();
+        self.undecoded.extend_from_slice(chunk);
+        self.reader.consume(read);
+
+        let valid = match std::str::from_utf8(&self.undecoded) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() && read > 0 => err.valid_up_to(),
+            Err(_) => return Err(unreadable("stream did not contain valid UTF-8")),
+        };
+        let rest = self.undecoded.split_off(valid);
+        self.pending
+            .push_str(std::str::from_utf8(&self.undecoded).unwrap_or_default());
+        self.undecoded = rest;
+        Ok(read)
+    }
+
+    /// The text of `spanned`, which has to be part of `pending`.
+    fn lexeme(&self, spanned: &SpannedToken) -> &str {
+        &self.pending[spanned.start.offset - self.start.offset..spanned.end - self.start.offset]
+    }
+
+    /// Moves the position of an error found in `pending` to be relative to the whole program.
+    fn shift_error(&self, err: TokenParseError) -> TokenParseError {
+        TokenParseError {
+            position: err.position.map(|position| shift(position, self.start)),
+            ..err
+        }
+    }
+}
+
+impl<D: Dialect, R: BufRead> Iterator for TokenReader<D, R> {
+    type Item = Result<BFToken, TokenParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.tokens.is_empty() && !self.done {
+            if let Err(err) = self.fill() {
+                self.done = true;
+                self.error = Some(err);
+            }
+        }
+        match self.tokens.pop_front() {
+            Some(spanned) => Some(Ok(spanned.token)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+/// Joins `lexemes` with `separator`, starting a new line instead when a line would get wider than `line_width`.
+pub(crate) fn join_lexemes(
+    lexemes: impl IntoIterator<Item = String>,
+    separator: &str,
+    line_width: usize,
+) -> String {
+    let separator_width = separator.chars().count();
+    let mut program = String::new();
+    let mut width = 0;
+    for lexeme in lexemes {
+        let lexeme_width = lexeme.chars().count();
+        if width > 0 {
+            if width + separator_width + lexeme_width > line_width {
+                program.push('\n');
+                width = 0;
+            } else {
+                program += separator;
+                width += separator_width;
+            }
+        }
+        program += &lexeme;
+        width += lexeme_width;
+    }
+    program
+}
+
+/// Moves `position`, relative to a text starting at `start`, to be relative to the text `start` is part of.
+fn shift(position: SourcePosition, start: SourcePosition) -> SourcePosition {
+    SourcePosition {
+        offset: position.offset + start.offset,
+        line: position.line + start.line - 1,
+        column: if position.line == 1 {
+            position.column + start.column - 1
+        } else {
+            position.column
+        },
+    }
+}
+
+/// Position right after `text`, which starts at `start`.
+fn advance(start: SourcePosition, text: &str) -> SourcePosition {
+    let mut end = start;
+    end.offset += text.len();
+    for character in text.chars() {
+        if character == '\n' {
+            end.line += 1;
+            end.column = 1;
+        } else {
+            end.column += 1;
+        }
+    }
+    end
+}
+
+/// Iterates over every character of `source` alongside its position.
+pub(crate) fn positioned_chars(source: &str) -> impl Iterator<Item = (SourcePosition, char)> + '_ {
+    let mut position = SourcePosition::default();
+    source.char_indices().map(move |(offset, character)| {
+        position.offset = offset;
+        let current = position;
+        if character == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+        }
+        (current, character)
+    })
+}
+
+/// Groups every `cutoff` characters of `source` that are part of `valid_characters`, also returning where the
+/// last, incomplete group starts, or else where the word `source` ends in starts (as more text could turn it
+/// into the start of a group).
+///
+/// Each group starts where the word holding its first character starts, so "Ook. Ook?" is reported as a
+/// whole instead of just ". ?".
+pub(crate) fn group_valid_chars(
+    source: &str,
+    valid_characters: &str,
+    cutoff: usize,
+) -> (Vec<(String, SourcePosition, usize)>, Option<SourcePosition>) {
+    let mut groups = Vec::new();
+    let mut current = String::new();
+    let mut group_start: Option<SourcePosition> = None;
+    let mut word_start: Option<SourcePosition> = None;
+
+    for (position, character) in positioned_chars(source) {
+        if character.is_whitespace() {
+            word_start = None;
+            continue;
+        }
+        let word = *word_start.get_or_insert(position);
+        if !valid_characters.contains(character) {
+            continue;
+        }
+        word_start = None;
+        group_start.get_or_insert(word);
+        current.push(character);
+
+        if current.chars().count() == cutoff {
+            let end = position.offset + character.len_utf8();
+            groups.push((
+                std::mem::take(&mut current),
+                group_start.take().unwrap(),
+                end,
+            ));
+        }
+    }
+
+    (groups, group_start.or(word_start))
+}
+
+/// Turns the result of [`Tokenizer::lex`] on a whole program into the tokens of that program.
+pub(crate) fn finish_lexing(
+    source: &str,
+    (tokens, dangling): (Vec<SpannedToken>, Option<SourcePosition>),
+) -> Result<Vec<SpannedToken>, TokenParseError> {
+    if let Some(start) = dangling {
+        return Err(TokenParseError::new(
+            ParseErrorKind::DanglingHalfToken,
+            Some(start),
+            source[start.offset..].trim_end(),
+        ));
+    }
+    check_brackets(source, &tokens)?;
+    Ok(tokens)
+}
+
+/// Makes sure every bracket in `tokens` has a matching counterpart.
+pub(crate) fn check_brackets(source: &str, tokens: &[SpannedToken]) -> Result<(), TokenParseError> {
+    let mut open: Vec<&SpannedToken> = Vec::new();
+
+    for token in tokens {
+        match token.token {
+            BFToken::JumpForwards => open.push(token),
+            BFToken::JumpBackwards if open.pop().is_none() => {
+                return Err(unbalanced_bracket(source, token));
+            }
+            _ => {}
+        }
+    }
+
+    match open.pop() {
+        Some(token) => Err(unbalanced_bracket(source, token)),
+        None => Ok(()),
+    }
+}
+
+fn unbalanced_bracket(source: &str, token: &SpannedToken) -> TokenParseError {
+    TokenParseError::new(
+        ParseErrorKind::UnbalancedBracket,
+        Some(token.start),
+        &source[token.span()],
+    )
 }
 
 macro_rules! single_char_tokenizer {
-    ($type: ty, $add: expr, $sub: expr, $left: expr, $right: expr, $print: expr, $input: expr, $forward: expr, $backward: expr) => {
-        fn to_tokens(iterator: String) -> Result<Vec<BFToken>, TokenParseError> {
-            Ok(iterator
-                .chars()
-                .map(|token| {
-                    return match token {
+    ($type: ty, $add: expr, $sub: expr, $left: expr, $right: expr, $print: expr, $input: expr, $forward: expr, $backward: expr $(, debug: $debug: expr)?) => {
+        fn to_spanned_tokens(source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+            finish_lexing(source, Self::lex(source)?)
+        }
+        fn lex(iterator: &str) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+            let tokens: Vec<SpannedToken> = positioned_chars(iterator)
+                .filter_map(|(position, token)| {
+                    let token_type = match token {
                         $add => BFToken::CellAdd,
                         $sub => BFToken::CellSubtract,
                         $right => BFToken::PtrRight,
@@ -36,11 +724,18 @@ macro_rules! single_char_tokenizer {
                         $input => BFToken::Input,
                         $forward => BFToken::JumpForwards,
                         $backward => BFToken::JumpBackwards,
-                        _ => BFToken::NoOP,
+                        $($debug => BFToken::Debug,)?
+                        _ => return None,
                     };
+                    Some(SpannedToken {
+                        token: token_type,
+                        start: position,
+                        end: position.offset + token.len_utf8(),
+                    })
                 })
-                .filter(|x| *x != BFToken::NoOP)
-                .collect())
+                .collect();
+
+            Ok((tokens, None))
         }
     };
 }
@@ -49,47 +744,52 @@ macro_rules! single_char_tokenizer {
 /// $cutoff is necessary to interpret how many characters need to be interpreted at once (e.g.: Ook. Ook. (2 '.' characters))
 macro_rules! multi_char_tokenizer {
     ($type: ty, $cutoff: expr, $valid_characters: expr, $add: expr, $sub: expr, $left: expr, $right: expr, $print: expr, $input: expr, $forward: expr, $backward: expr) => {
-        fn to_tokens(iterator: String) -> Result<Vec<BFToken>, TokenParseError> {
-            let valid_chars: Vec<char> = iterator
-                .chars()
-                .filter(|c| $valid_characters.contains(*c))
-                .collect();
-
-            if valid_chars.len() % $cutoff != 0 {
-                return Err(TokenParseError);
-            }
-
-            let mut tokens: Vec<BFToken> = Vec::new();
+        fn to_spanned_tokens(source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+            let (tokens, dangling) = Self::lex(source)?;
+            // A word without any of the valid characters at the end of the program is just a comment
+            let dangling = dangling.filter(|start| {
+                source[start.offset..].contains(|character| $valid_characters.contains(character))
+            });
+            finish_lexing(source, (tokens, dangling))
+        }
+        fn lex(
+            iterator: &str,
+        ) -> Result<(Vec<SpannedToken>, Option<SourcePosition>), TokenParseError> {
+            let mut tokens: Vec<SpannedToken> = Vec::new();
 
-            for i in (0..valid_chars.len()).step_by($cutoff) {
-                tokens.push(
-                    match valid_chars[i..i + $cutoff]
-                        .iter()
-                        .collect::<String>()
-                        .as_str()
-                    {
-                        $add => BFToken::CellAdd,
-                        $sub => BFToken::CellSubtract,
-                        $left => BFToken::PtrLeft,
-                        $right => BFToken::PtrRight,
-                        $print => BFToken::Print,
-                        $input => BFToken::Input,
-                        $forward => BFToken::JumpForwards,
-                        $backward => BFToken::JumpBackwards,
-                        _ => BFToken::NoOP,
-                    },
-                );
+            let (groups, dangling) = group_valid_chars(iterator, $valid_characters, $cutoff);
+            for (group, start, end) in groups {
+                let token_type = match group.as_str() {
+                    $add => BFToken::CellAdd,
+                    $sub => BFToken::CellSubtract,
+                    $left => BFToken::PtrLeft,
+                    $right => BFToken::PtrRight,
+                    $print => BFToken::Print,
+                    $input => BFToken::Input,
+                    $forward => BFToken::JumpForwards,
+                    $backward => BFToken::JumpBackwards,
+                    _ => {
+                        return Err(TokenParseError::new(
+                            ParseErrorKind::UnknownWord,
+                            Some(start),
+                            &iterator[start.offset..end],
+                        ))
+                    }
+                };
+                tokens.push(SpannedToken {
+                    token: token_type,
+                    start,
+                    end,
+                });
             }
 
-            tokens.retain(|x| *x != BFToken::NoOP);
-
-            Ok(tokens)
+            Ok((tokens, dangling))
         }
     };
 }
 
 macro_rules! token_to_string {
-    ($add: expr, $sub: expr, $left: expr, $right: expr, $print: expr, $input: expr, $forwards: expr, $backwards: expr) => {
+    ($add: expr, $sub: expr, $left: expr, $right: expr, $print: expr, $input: expr, $forwards: expr, $backwards: expr $(, debug: $debug: expr)?) => {
         paste::item! {
             fn token_to_string(element: BFToken) -> Result<String, TokenParseError> {
                 return Ok(String::from(match element {
@@ -101,7 +801,14 @@ macro_rules! token_to_string {
                     BFToken::Input => $input,
                     BFToken::JumpForwards => $forwards,
                     BFToken::JumpBackwards => $backwards,
-                    _ => return Err(TokenParseError),
+                    $(BFToken::Debug => $debug,)?
+                    _ => {
+                        return Err(TokenParseError::new(
+                            ParseErrorKind::UnrepresentableToken,
+                            None,
+                            &format!("{element:?}"),
+                        ))
+                    }
                 }));
             }
         }
@@ -0,0 +1,232 @@
+#![doc = r"Recording every executed instruction to compare runs"]
+#![cfg_attr(docsrs, feature(doc_cfg))]
+
+use crate::{
+    cell::Cell,
+    encoding::{
+        read_header, read_len, read_signed_varint, token_code, token_from_code, write_header,
+        write_signed_varint, write_varint, DecodeError,
+    },
+    token::BFToken,
+};
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+const MAGIC: &[u8; 4] = b"BFIT";
+const VERSION: u8 = 1;
+
+/// One executed instruction.
+///
+/// `cell_before` is the cell under the pointer before the instruction ran and `cell_after` the cell under the
+/// pointer after it ran, so for `<` and `>` they are two different cells. Pointers are positions relative to
+/// the starting cell, so they stay comparable when a growing tape adds cells to the left.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceEvent<C: Cell = u8> {
+    pub instruction: usize,
+    pub token: BFToken,
+    pub pointer_before: isize,
+    pub pointer_after: isize,
+    pub cell_before: C,
+    pub cell_after: C,
+}
+
+/// Receives a [`TraceEvent`] after every instruction a runtime executes.
+///
+/// An error from [`Tracer::trace`] is returned by the runtime once the instruction finished, with the
+/// instruction counter already past it, so the runtime can keep going after it (see
+/// [`Runner::next_instruction`](crate::runtime::Runner::next_instruction)).
+pub trait Tracer<C: Cell> {
+    fn trace(&mut self, event: &TraceEvent<C>) -> io::Result<()>;
+    /// Writes out anything the tracer buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`Tracer`] shared between a runtime and its clones, set as the runtime's `tracer`.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+///
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime.tracer = Some(TraceHook::new(TextTracer::new(std::io::stderr())));
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("+>+")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+///     .expect("Failed running program");
+/// ```
+pub struct TraceHook<C: Cell = u8> {
+    tracer: Arc<Mutex<dyn Tracer<C> + Send>>,
+}
+
+impl<C: Cell> TraceHook<C> {
+    pub fn new(tracer: impl Tracer<C> + Send + 'static) -> Self {
+        Self {
+            tracer: Arc::new(Mutex::new(tracer)),
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.tracer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .flush()
+    }
+
+    pub(crate) fn trace(&self, event: &TraceEvent<C>) -> io::Result<()> {
+        self.tracer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .trace(event)
+    }
+}
+
+impl<C: Cell> Clone for TraceHook<C> {
+    fn clone(&self) -> Self {
+        Self {
+            tracer: Arc::clone(&self.tracer),
+        }
+    }
+}
+
+impl<C: Cell> fmt::Debug for TraceHook<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceHook").finish_non_exhaustive()
+    }
+}
+
+/// Writes one line per instruction, e.g.: `4 PtrRight ptr=0->1 cell=3->0`.
+///
+/// Traces of two runs can be compared with any line-based diff tool.
+#[derive(Debug)]
+pub struct TextTracer<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<C: Cell, W: Write> Tracer<C> for TextTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<C>) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{} {:?} ptr={}->{} cell={}->{}",
+            event.instruction,
+            event.token,
+            event.pointer_before,
+            event.pointer_after,
+            event.cell_before,
+            event.cell_after
+        )
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes a compact binary trace, read back with [`decode_trace`].
+///
+/// The trace starts with a header naming the cell type, followed by one varint-encoded record per instruction.
+#[derive(Debug)]
+pub struct BinaryTracer<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BinaryTracer<W> {
+    /// Writes the header for `C` cells to `writer`.
+    pub fn new<C: Cell>(mut writer: W) -> io::Result<Self> {
+        let mut header = Vec::new();
+        write_header::<C>(&mut header, MAGIC, VERSION);
+        writer.write_all(&header)?;
+        Ok(Self {
+            writer,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl<C: Cell, W: Write> Tracer<C> for BinaryTracer<W> {
+    fn trace(&mut self, event: &TraceEvent<C>) -> io::Result<()> {
+        self.buffer.clear();
+        write_varint(&mut self.buffer, event.instruction as u64);
+        self.buffer.push(token_code(event.token));
+        write_signed_varint(&mut self.buffer, event.pointer_before as i64);
+        write_signed_varint(&mut self.buffer, event.pointer_after as i64);
+        event.cell_before.encode(&mut self.buffer);
+        event.cell_after.encode(&mut self.buffer);
+        self.writer.write_all(&self.buffer)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads a trace written by [`BinaryTracer`].
+///
+/// Fails if `bytes` is not a trace, was written for another cell type or is truncated.
+///
+/// ```rust
+/// use libbfi::prelude::*;
+/// use std::sync::{Arc, Mutex};
+///
+/// #[derive(Clone, Default)]
+/// struct Shared(Arc<Mutex<Vec<u8>>>);
+///
+/// impl std::io::Write for Shared {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         self.0.lock().unwrap().write(buf)
+///     }
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let trace = Shared::default();
+/// let mut runtime = BrainfuckRuntime::new();
+/// runtime.tracer = Some(TraceHook::new(
+///     BinaryTracer::new::<u8>(trace.clone()).expect("Failed writing trace"),
+/// ));
+/// runtime
+///     .add_tokens(Brainfuck::to_tokens(String::from("++>")).expect("Failed parsing program"))
+///     .expect("Failed loading program")
+///     .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+///     .expect("Failed running program");
+///
+/// let events = decode_trace::<u8>(&trace.0.lock().unwrap()).expect("Failed reading trace");
+/// assert_eq!(events.len(), 3);
+/// assert_eq!(events[2].token, BFToken::PtrRight);
+/// assert_eq!((events[2].cell_before, events[2].cell_after), (2, 0));
+/// ```
+pub fn decode_trace<C: Cell>(bytes: &[u8]) -> Result<Vec<TraceEvent<C>>, DecodeError> {
+    let mut bytes = read_header::<C>(bytes, MAGIC, VERSION)?;
+
+    let mut events: Vec<TraceEvent<C>> = Vec::new();
+    while !bytes.is_empty() {
+        let instruction = read_len(&mut bytes)?;
+        let (&code, rest) = bytes.split_first().ok_or(DecodeError::Corrupted)?;
+        bytes = rest;
+        events.push(TraceEvent {
+            instruction,
+            token: token_from_code(code).ok_or(DecodeError::Corrupted)?,
+            pointer_before: read_position(&mut bytes)?,
+            pointer_after: read_position(&mut bytes)?,
+            cell_before: C::decode(&mut bytes).ok_or(DecodeError::Corrupted)?,
+            cell_after: C::decode(&mut bytes).ok_or(DecodeError::Corrupted)?,
+        });
+    }
+    Ok(events)
+}
+
+fn read_position(input: &mut &[u8]) -> Result<isize, DecodeError> {
+    read_signed_varint(input)
+        .and_then(|value| isize::try_from(value).ok())
+        .ok_or(DecodeError::Corrupted)
+}
@@ -7,7 +7,8 @@ const DEFAULT_TEST_ERROR: &str = "Failed to read tokens";
 fn test_adding_characters() {
     let mut runtime = BrainfuckRuntime::new();
     runtime
-        .add_tokens(Brainfuck::to_tokens(String::from("+a-b>c<d[e]f")).expect(DEFAULT_TEST_ERROR));
+        .add_tokens(Brainfuck::to_tokens(String::from("+a-b>c<d[e]f")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
 
     assert_eq!(
         runtime.instruction_stack,
@@ -27,7 +28,9 @@ fn test_to_ook() {
     let mut memory = BrainfuckRuntime::new();
     memory
         .add_tokens(Brainfuck::to_tokens(String::from("+a-b>c<d[e]f")).expect(DEFAULT_TEST_ERROR))
-        .add_tokens(Ook::to_tokens(String::from("Ook. Ook. Ook. Ook.")).expect(DEFAULT_TEST_ERROR));
+        .expect(DEFAULT_TEST_ERROR)
+        .add_tokens(Ook::to_tokens(String::from("Ook. Ook. Ook. Ook.")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
 
     assert_eq!(
         memory.instruction_stack,
@@ -43,3 +46,1216 @@ fn test_to_ook() {
         ]
     )
 }
+
+#[test]
+fn test_runtime_errors() {
+    let mut runtime = BrainfuckRuntime::with_memory_size(2);
+
+    let result = runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+<")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(
+        result,
+        Err(RuntimeError::PointerOutOfBounds { instruction: 1 })
+    ));
+    assert_eq!(runtime.instruction, 1);
+
+    let result = runtime
+        .clean_env()
+        .add_tokens(vec![BFToken::CellAdd, BFToken::JumpBackwards]);
+    assert!(matches!(
+        result,
+        Err(RuntimeError::UnmatchedBracket { instruction: 1 })
+    ));
+    assert!(runtime.instruction_stack.is_empty());
+
+    let result = runtime
+        .clean_env()
+        .add_tokens(vec![BFToken::Input])
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(result, Err(RuntimeError::Eof)));
+
+    // An empty tape has no cell to work with, but must not take the host down
+    let tokens = Brainfuck::to_tokens("+").expect(DEFAULT_TEST_ERROR);
+    let mut runtime = BrainfuckRuntime::with_memory_size(0);
+    let result = runtime
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(
+        result,
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+    let mut ir_runtime = IrRuntime::with_memory_size(0);
+    let result = ir_runtime
+        .add_tokens(tokens)
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(
+        result,
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = Ook::to_tokens(String::from("Ook. Ook.\nOok! Ook? Ook.")).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::DanglingHalfToken);
+    assert_eq!(error.lexeme, "Ook.");
+    assert_eq!(
+        error.position,
+        Some(SourcePosition {
+            offset: 20,
+            line: 2,
+            column: 11
+        })
+    );
+
+    let error = Blub::to_tokens(String::from("Blub. Blub. Blub? Blub?")).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::UnknownWord);
+    assert_eq!(error.lexeme, "Blub? Blub?");
+
+    let error = Brainfuck::to_tokens(String::from("+[[-]")).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::UnbalancedBracket);
+    assert_eq!(error.position.map(|position| position.column), Some(2));
+    assert_eq!(
+        error.to_string(),
+        "unbalanced bracket `[` at line 1, column 2 (byte 1)"
+    );
+}
+
+#[test]
+fn test_nested_loops() {
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::new();
+
+    runtime
+        .add_tokens(
+            Brainfuck::to_tokens(String::from("++[>+++[>++++<-]<-]>>.")).expect(DEFAULT_TEST_ERROR),
+        )
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+
+    assert_eq!(runtime.memory[2], 24);
+    assert_eq!(output, vec![24]);
+}
+
+const HELLO_WORLD: &str = ">++++++++[<+++++++++>-]<.>++++[<+++++++>-]<+.+++++++..+++.>>++++++[<+++++++>-]<++.------------.>++++++[<+++++++++>-]<+.<.+++.------.--------.>>>++++[<++++++++>-]<+.";
+
+#[test]
+fn test_ir_runtime() {
+    let tokens = Brainfuck::to_tokens(String::from(HELLO_WORLD)).expect(DEFAULT_TEST_ERROR);
+    let mut expected: Vec<u8> = Vec::new();
+    let mut output: Vec<u8> = Vec::new();
+
+    BrainfuckRuntime::new()
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut expected)
+        .expect(DEFAULT_TEST_ERROR);
+
+    let mut runtime = IrRuntime::new();
+    runtime
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+
+    assert_eq!(output, expected);
+    assert!(runtime.program.len() < tokens.len());
+    assert!(matches!(
+        ir::compile(&[BFToken::JumpForwards]),
+        Err(RuntimeError::UnmatchedBracket { instruction: 0 })
+    ));
+
+    // Moves that cancel out still go through every cell on the way
+    let tokens = Brainfuck::to_tokens("<>+.").expect(DEFAULT_TEST_ERROR);
+    assert_eq!(
+        ir::compile(&tokens).expect(DEFAULT_TEST_ERROR)[..2],
+        [Instruction::Move(-1), Instruction::Move(1)]
+    );
+    assert!(matches!(
+        BrainfuckRuntime::new()
+            .add_tokens(tokens.clone())
+            .expect(DEFAULT_TEST_ERROR)
+            .run_full_stack(&mut std::io::empty(), &mut std::io::sink()),
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+    assert!(matches!(
+        IrRuntime::new()
+            .add_tokens(tokens)
+            .expect(DEFAULT_TEST_ERROR)
+            .run_full_stack(&mut std::io::empty(), &mut std::io::sink()),
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+}
+
+#[test]
+fn test_peephole_optimizer() {
+    let source = String::from(">+++++[>+++++++<-]>[->+>++<<]>[>]<<[-]+++[<]>>>.>.");
+    let tokens = Brainfuck::to_tokens(source).expect(DEFAULT_TEST_ERROR);
+    let mut expected: Vec<u8> = Vec::new();
+    let mut output: Vec<u8> = Vec::new();
+
+    BrainfuckRuntime::new()
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut expected)
+        .expect(DEFAULT_TEST_ERROR);
+
+    let mut runtime = IrRuntime::new();
+    runtime.optimize = true;
+    runtime
+        .add_tokens(tokens)
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+
+    assert_eq!(output, expected);
+    assert!(runtime.program.contains(&Instruction::Clear));
+    assert!(runtime.program.contains(&Instruction::Scan(1)));
+    assert!(runtime.program.contains(&Instruction::MulAdd {
+        offset: 2,
+        factor: 2
+    }));
+}
+
+#[test]
+fn test_eof_policy() {
+    let tokens = Brainfuck::to_tokens(String::from("+,>+,,")).expect(DEFAULT_TEST_ERROR);
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.add_tokens(tokens).expect(DEFAULT_TEST_ERROR);
+
+    for (policy, expected) in [
+        (EofPolicy::Unchanged, [b'a', 1]),
+        (EofPolicy::Zero, [b'a', 0]),
+        (EofPolicy::MaxValue, [b'a', 255]),
+    ] {
+        runtime.instruction = 0;
+        runtime.pointer = 0;
+        runtime.memory.fill(0);
+        runtime.eof_policy = policy;
+        runtime
+            .run_full_stack(&mut "a".as_bytes(), &mut std::io::sink())
+            .expect(DEFAULT_TEST_ERROR);
+        assert_eq!(runtime.memory[..2], expected);
+    }
+
+    let mut input = "xyz".as_bytes();
+    runtime.instruction = 0;
+    runtime.pointer = 0;
+    runtime.eof_policy = EofPolicy::Error;
+    runtime
+        .run_full_stack(&mut input, &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(runtime.memory[..2], [b'x', b'z']);
+    assert!(input.is_empty());
+}
+
+#[test]
+fn test_output_mode() {
+    let mut runtime = BrainfuckRuntime::new();
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("-.")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+
+    for (mode, expected) in [
+        (OutputMode::Byte, vec![0xFF]),
+        (OutputMode::Decimal, b"255\n".to_vec()),
+        (OutputMode::Utf8, "ÿ".as_bytes().to_vec()),
+    ] {
+        let mut output: Vec<u8> = Vec::new();
+        runtime.instruction = 0;
+        runtime.memory[0] = 0;
+        runtime.output_mode = mode;
+        runtime
+            .run_full_stack(&mut std::io::empty(), &mut output)
+            .expect(DEFAULT_TEST_ERROR);
+        assert_eq!(output, expected);
+    }
+}
+
+#[derive(Default)]
+struct FlushCounter {
+    data: Vec<u8>,
+    flushes: usize,
+}
+
+impl std::io::Write for FlushCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_flush_policy() {
+    let tokens = Brainfuck::to_tokens(String::from("++++++++++...,...")).expect(DEFAULT_TEST_ERROR);
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.add_tokens(tokens).expect(DEFAULT_TEST_ERROR);
+
+    for (policy, expected) in [
+        (FlushPolicy::EveryByte, 7),
+        (FlushPolicy::Newline, 5),
+        (FlushPolicy::BeforeInput, 2),
+        (FlushPolicy::EndOfRun, 1),
+    ] {
+        let mut output = FlushCounter::default();
+        runtime.instruction = 0;
+        runtime.memory[0] = 0;
+        runtime.flush_policy = policy;
+        runtime
+            .run_full_stack(&mut "a".as_bytes(), &mut output)
+            .expect(DEFAULT_TEST_ERROR);
+        assert_eq!(output.data, b"\n\n\naaa");
+        assert_eq!(output.flushes, expected, "{policy:?}");
+    }
+}
+
+#[test]
+fn test_cell_types() {
+    let tokens =
+        Brainfuck::to_tokens(String::from("-.>++++++++++++[<++++>-]<.")).expect(DEFAULT_TEST_ERROR);
+
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::<u16>::with_cells(2);
+    runtime.output_mode = OutputMode::Decimal;
+    runtime
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, b"65535\n47\n");
+
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::<i32>::with_cells(2);
+    runtime.output_mode = OutputMode::Decimal;
+    runtime
+        .add_tokens(tokens)
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, b"-1\n47\n");
+
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.arithmetic = Arithmetic::Saturating;
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("--->+++")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(runtime.memory[..2], [0, 3]);
+}
+
+#[cfg(feature = "bignum")]
+#[test]
+fn test_bignum_cells() {
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::<num_bigint::BigInt>::with_cells(10);
+    runtime.output_mode = OutputMode::Decimal;
+    runtime
+        .add_tokens(
+            Brainfuck::to_tokens(String::from(
+                "++++[>++++++++<-]>[>++++++++<-]>[>++++++++<-]>[>++++++++<-]>[>++++++++<-]>.--.",
+            ))
+            .expect(DEFAULT_TEST_ERROR),
+        )
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, b"131072\n131070\n");
+}
+
+#[test]
+fn test_growable_tape() {
+    let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u32>::with_max_size(3));
+
+    let result = runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+<-<<")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(
+        result,
+        Err(RuntimeError::PointerOutOfBounds { instruction: 4 })
+    ));
+    assert_eq!(runtime.memory.len(), 3);
+    assert_eq!(runtime.memory.origin(), 2);
+    assert_eq!(runtime.memory.get(0), Some(&1));
+    assert_eq!(runtime.memory.get(-1), Some(&u32::MAX));
+
+    runtime.clean_env();
+    assert_eq!(runtime.memory.len(), 1);
+}
+
+#[test]
+fn test_pointer_policy() {
+    let tokens = Brainfuck::to_tokens(String::from("<+>>>++")).expect(DEFAULT_TEST_ERROR);
+
+    for (policy, expected) in [
+        (PointerPolicy::Wrap, [0, 0, 3]),
+        (PointerPolicy::Clamp, [1, 0, 2]),
+    ] {
+        let mut runtime = BrainfuckRuntime::with_memory_size(3);
+        runtime.pointer_policy = policy;
+        runtime
+            .add_tokens(tokens.clone())
+            .expect(DEFAULT_TEST_ERROR)
+            .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+            .expect(DEFAULT_TEST_ERROR);
+        assert_eq!(runtime.memory, expected, "{policy:?}");
+    }
+
+    let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u8>::new());
+    runtime.pointer_policy = PointerPolicy::Error;
+    let result = runtime
+        .add_tokens(tokens)
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink());
+    assert!(matches!(
+        result,
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+
+    // Wrapping around an empty tape has nowhere to go
+    let mut runtime = BrainfuckRuntime::with_memory_size(0);
+    runtime.pointer_policy = PointerPolicy::Wrap;
+    assert!(matches!(
+        runtime.op_ptr_left(),
+        Err(RuntimeError::PointerOutOfBounds { instruction: 0 })
+    ));
+}
+
+#[test]
+fn test_run_limits() {
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::new();
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+++[.-]")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+
+    let state = runtime
+        .run_with_limit(&mut std::io::empty(), &mut output, 6)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(state, RunState::StepLimit);
+    assert_eq!(output, vec![3]);
+
+    let state = runtime
+        .run_with_limit(&mut std::io::empty(), &mut output, 100)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(state, RunState::Finished);
+    assert_eq!(output, vec![3, 2, 1]);
+
+    let mut runtime = IrRuntime::new();
+    let state = runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+[]")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_until(
+            &mut std::io::empty(),
+            &mut std::io::sink(),
+            std::time::Instant::now() + std::time::Duration::from_millis(10),
+        )
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(state, RunState::Deadline);
+    assert!(!runtime.is_finished());
+}
+
+#[test]
+fn test_session() {
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.eof_policy = EofPolicy::Zero;
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from(",[.,]+++")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+
+    let mut session = Session::new(runtime);
+    let mut output: Vec<u8> = Vec::new();
+
+    for chunk in [&b"ab"[..], b"c"] {
+        session.feed(chunk);
+        loop {
+            match session.resume().expect(DEFAULT_TEST_ERROR) {
+                RunState::OutputReady => output.extend(session.take_output()),
+                RunState::NeedsInput => break,
+                state => panic!("unexpected state {state:?}"),
+            }
+        }
+    }
+    assert_eq!(output, b"abc");
+
+    session.close_input();
+    assert_eq!(
+        session.resume_with_limit(2).expect(DEFAULT_TEST_ERROR),
+        RunState::StepLimit
+    );
+    assert_eq!(
+        session.resume().expect(DEFAULT_TEST_ERROR),
+        RunState::Finished
+    );
+    assert_eq!(session.runtime.memory[0], 3);
+}
+
+#[test]
+fn test_snapshot() {
+    let tokens = Brainfuck::to_tokens(String::from("<<-->+++[>++<-]>.")).expect(DEFAULT_TEST_ERROR);
+    let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u16>::new());
+    runtime
+        .add_tokens(tokens)
+        .expect(DEFAULT_TEST_ERROR)
+        .run_with_limit(&mut std::io::empty(), &mut std::io::sink(), 12)
+        .expect(DEFAULT_TEST_ERROR);
+
+    let bytes = runtime.snapshot().to_bytes();
+    let mut restored = BrainfuckRuntime::with_tape(GrowableTape::<u16>::new());
+    restored
+        .restore(Snapshot::from_bytes(&bytes).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(restored.snapshot(), runtime.snapshot());
+
+    let mut expected: Vec<u8> = Vec::new();
+    let mut output: Vec<u8> = Vec::new();
+    runtime
+        .run_full_stack(&mut std::io::empty(), &mut expected)
+        .expect(DEFAULT_TEST_ERROR);
+    restored
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, expected);
+
+    assert_eq!(
+        Snapshot::<u8>::from_bytes(&bytes),
+        Err(SnapshotError::CellTypeMismatch {
+            expected: "u8",
+            found: String::from("u16")
+        })
+    );
+    assert_eq!(
+        Snapshot::<u16>::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(SnapshotError::Corrupted)
+    );
+    let snapshot = Snapshot::<u16>::from_bytes(&bytes).expect(DEFAULT_TEST_ERROR);
+    assert!(matches!(
+        BrainfuckRuntime::<u16>::with_cells(10).restore(snapshot),
+        Err(SnapshotError::IncompatibleTape)
+    ));
+    assert_eq!(
+        Snapshot::<u16>::from_bytes_with_limit(&bytes, 2),
+        Err(SnapshotError::TapeTooLarge { cells: 3, limit: 2 })
+    );
+
+    // Crafted headers must be rejected without overflowing or allocating the claimed tape
+    let varint = |mut value: u64| {
+        let mut out: Vec<u8> = Vec::new();
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+        out
+    };
+    let header = [b"BFIS".as_slice(), &[1, 2], b"u8", &[0, 0, 0, 0]].concat();
+    let overflowing = [
+        header.clone(),
+        varint(10),
+        varint(u64::MAX - 1),
+        varint(u64::MAX - 1),
+    ]
+    .concat();
+    assert_eq!(
+        Snapshot::<u8>::from_bytes(&overflowing),
+        Err(SnapshotError::Corrupted)
+    );
+    let huge = [header, varint(1 << 40), varint(1 << 40), varint(0)].concat();
+    assert!(matches!(
+        Snapshot::<u8>::from_bytes(&huge),
+        Err(SnapshotError::TapeTooLarge { .. })
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_snapshot_json() {
+    let mut runtime = BrainfuckRuntime::new();
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("++>+")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_with_limit(&mut std::io::empty(), &mut std::io::sink(), 3)
+        .expect(DEFAULT_TEST_ERROR);
+
+    let json = serde_json::to_string(&runtime.snapshot()).expect(DEFAULT_TEST_ERROR);
+    let snapshot: Snapshot<u8> = serde_json::from_str(&json).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(snapshot, runtime.snapshot());
+}
+
+#[test]
+fn test_debugger() {
+    let mut runtime = BrainfuckRuntime::new();
+    runtime
+        .add_tokens(
+            Brainfuck::to_tokens(String::from("++[>+++[>+<-]<-]>>.")).expect(DEFAULT_TEST_ERROR),
+        )
+        .expect(DEFAULT_TEST_ERROR);
+
+    let mut debugger = Debugger::new(runtime);
+    let (input, output) = (&mut std::io::empty(), &mut std::io::sink());
+
+    let token = debugger.add_breakpoint(Breakpoint::Token(BFToken::JumpForwards));
+    assert_eq!(
+        debugger.resume(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Breakpoint(token)
+    );
+    assert_eq!(debugger.runtime.instruction, 2);
+    debugger.remove_breakpoint(token);
+
+    let pointer = debugger.add_breakpoint(Breakpoint::Pointer(2));
+    assert_eq!(
+        debugger.resume(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Breakpoint(pointer)
+    );
+    assert_eq!(debugger.runtime.instruction, 9);
+    debugger.remove_breakpoint(pointer);
+
+    assert_eq!(
+        debugger.step_out(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Step
+    );
+    assert_eq!(debugger.runtime.instruction, 13);
+    assert_eq!(debugger.runtime.memory[2], 3);
+
+    debugger.add_breakpoint(Breakpoint::Instruction(17));
+    assert_eq!(
+        debugger.step_over(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Step
+    );
+    assert_eq!(debugger.breakpoints().count(), 1);
+    assert_eq!(
+        debugger.resume(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Breakpoint(2)
+    );
+    assert_eq!(
+        debugger.resume(input, output).expect(DEFAULT_TEST_ERROR),
+        StopReason::Finished
+    );
+    assert_eq!(debugger.runtime.memory[2], 6);
+}
+
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_debug_dump() {
+    let source = String::from("+++>++#<-#.");
+    assert!(!Brainfuck::to_tokens(source.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .contains(&BFToken::Debug));
+
+    let diagnostics = SharedBuffer::default();
+    let mut output: Vec<u8> = Vec::new();
+    let mut runtime = BrainfuckRuntime::with_memory_size(4);
+    runtime.debug_dump = Some(DebugDump::new(diagnostics.clone(), 1));
+    runtime
+        .add_tokens(DebugBrainfuck::to_tokens(source).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+
+    assert_eq!(output, vec![2]);
+    assert_eq!(
+        String::from_utf8(diagnostics.0.lock().unwrap().clone()).unwrap(),
+        "#6 ptr=1: 0:3 [1:2] 2:0\n#9 ptr=0: [0:2] 1:2\n"
+    );
+    assert_eq!(
+        DebugBrainfuck::token_to_string(BFToken::Debug),
+        Ok(String::from("#"))
+    );
+}
+
+#[test]
+fn test_reverse_stepping() {
+    let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u8>::new());
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from(",+<,[->+<]>.")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+
+    let mut debugger = Debugger::new(runtime);
+    debugger.record_history(10_000);
+    let mut input = "ab".as_bytes();
+    let mut output: Vec<u8> = Vec::new();
+
+    debugger
+        .resume(&mut input, &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, vec![b'a' + 1 + b'b']);
+    assert!(input.is_empty());
+
+    let id = debugger.add_breakpoint(Breakpoint::Instruction(4));
+    assert_eq!(debugger.step_back(3), 3);
+    assert_eq!(debugger.rewind_to_breakpoint(), StopReason::Breakpoint(id));
+    assert_eq!(debugger.runtime.pointer, 0);
+    assert_eq!(debugger.runtime.memory.get(-1), Some(&b'b'));
+    assert_eq!(debugger.runtime.memory.get(0), Some(&(b'a' + 1)));
+
+    assert_eq!(debugger.step_back(usize::MAX), 4);
+    assert_eq!(debugger.runtime.instruction, 0);
+    assert_eq!(debugger.runtime.pointer, 1);
+    assert_eq!(debugger.runtime.memory.get(0), Some(&0));
+    assert_eq!(debugger.runtime.memory.get(-1), Some(&0));
+    assert_eq!(debugger.history().count(), 0);
+
+    debugger.remove_breakpoint(id);
+    debugger
+        .resume(&mut input, &mut output)
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(output, vec![b'a' + 1 + b'b'; 2]);
+
+    // Cleaning the runtime by hand drops the left-grown cells the history refers to
+    assert!(debugger.history().count() > 0);
+    debugger.runtime.clean_env();
+    assert_eq!(debugger.step_back(1), 0);
+    assert_eq!(debugger.history().count(), 0);
+}
+
+#[test]
+fn test_tracing() {
+    let text = SharedBuffer::default();
+    let mut runtime = BrainfuckRuntime::with_memory_size(4);
+    runtime.tracer = Some(TraceHook::new(TextTracer::new(text.clone())));
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+[-]>")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+
+    assert_eq!(
+        String::from_utf8(text.0.lock().unwrap().clone()).unwrap(),
+        "0 CellAdd ptr=0->0 cell=0->1\n\
+         1 JumpForwards ptr=0->0 cell=1->1\n\
+         2 CellSubtract ptr=0->0 cell=1->0\n\
+         3 JumpBackwards ptr=0->0 cell=0->0\n\
+         4 PtrRight ptr=0->1 cell=0->0\n"
+    );
+
+    let binary = SharedBuffer::default();
+    let mut runtime = BrainfuckRuntime::<u16>::with_cells(4);
+    runtime.tracer = Some(TraceHook::new(
+        BinaryTracer::new::<u16>(binary.clone()).expect(DEFAULT_TEST_ERROR),
+    ));
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("-->+<[+]")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+
+    let bytes = binary.0.lock().unwrap().clone();
+    let events = decode_trace::<u16>(&bytes).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(events.len(), 10);
+    assert_eq!(
+        events[2],
+        TraceEvent {
+            instruction: 2,
+            token: BFToken::PtrRight,
+            pointer_before: 0,
+            pointer_after: 1,
+            cell_before: 65534,
+            cell_after: 0,
+        }
+    );
+    assert_eq!(events.last().map(|event| event.cell_after), Some(0));
+    assert!(matches!(
+        decode_trace::<u8>(&bytes),
+        Err(DecodeError::CellTypeMismatch { .. })
+    ));
+    assert_eq!(
+        decode_trace::<u16>(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::Corrupted)
+    );
+
+    // Pointers stay relative to the starting cell when the tape grows to the left
+    let binary = SharedBuffer::default();
+    let mut runtime = BrainfuckRuntime::with_tape(GrowableTape::<u8>::new());
+    runtime.tracer = Some(TraceHook::new(
+        BinaryTracer::new::<u8>(binary.clone()).expect(DEFAULT_TEST_ERROR),
+    ));
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("<<>")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+    let events = decode_trace::<u8>(&binary.0.lock().unwrap()).expect(DEFAULT_TEST_ERROR);
+    let pointers: Vec<(isize, isize)> = events
+        .iter()
+        .map(|event| (event.pointer_before, event.pointer_after))
+        .collect();
+    assert_eq!(pointers, vec![(0, -1), (-1, -2), (-2, -1)]);
+
+    // A failing tracer doesn't leave the runtime on an instruction that already ran
+    struct FailingTracer;
+    impl Tracer<u8> for FailingTracer {
+        fn trace(&mut self, _: &TraceEvent<u8>) -> std::io::Result<()> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.tracer = Some(TraceHook::new(FailingTracer));
+    runtime.profile = Some(Profile::new());
+    runtime
+        .add_tokens(Brainfuck::to_tokens(String::from("+")).expect(DEFAULT_TEST_ERROR))
+        .expect(DEFAULT_TEST_ERROR);
+    assert!(runtime
+        .next_instruction(&mut std::io::empty(), &mut std::io::sink())
+        .is_err());
+    assert_eq!(runtime.instruction, 1);
+    assert_eq!(runtime.memory[0], 1);
+    assert_eq!(runtime.profile.as_ref().map(Profile::total), Some(1));
+}
+
+#[test]
+fn test_profiler() {
+    let source = "++[\n  >+++ [-] clear\n<-]";
+    let spanned = Brainfuck::to_spanned_tokens(source).expect(DEFAULT_TEST_ERROR);
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.profile = Some(Profile::new());
+    runtime
+        .add_tokens(spanned.iter().map(|spanned| spanned.token).collect())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+
+    let profile = runtime.profile.as_ref().unwrap();
+    assert_eq!(profile.total(), 31);
+    assert_eq!(profile.count(8), 6);
+    assert_eq!(
+        profile.loops(&runtime.instruction_stack)[1],
+        LoopProfile {
+            start: 7,
+            end: 9,
+            entries: 2,
+            iterations: 6,
+            steps: 14,
+        }
+    );
+    assert_eq!(
+        profile.report(source, &spanned, 2),
+        "31 instructions executed\n\
+         hot loops:\n  \
+         1:3: 29 steps, 1 entries, 2 iterations: [ >+++ [-] clear <-]\n  \
+         2:8: 14 steps, 2 entries, 6 iterations: [-]\n\
+         hot instructions:\n  \
+         2:9: 6 times: -\n  \
+         2:10: 6 times: ]\n"
+    );
+}
+
+#[test]
+fn test_coverage() {
+    let source = "read ,\n[ echo .\n  , ]\n+[-]";
+    let spanned = Brainfuck::to_spanned_tokens(source).expect(DEFAULT_TEST_ERROR);
+    let tokens: Vec<BFToken> = spanned.iter().map(|spanned| spanned.token).collect();
+
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.coverage = Some(Coverage::new());
+    runtime.eof_policy = EofPolicy::Zero;
+    runtime
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+
+    let coverage = runtime.coverage.clone().unwrap();
+    assert_eq!(
+        coverage.loop_bodies(&tokens),
+        vec![(1, 4, false), (6, 8, true)]
+    );
+    assert_eq!(
+        coverage.to_lcov("cat.bf", &spanned),
+        "TN:\nSF:cat.bf\nBRDA:2,0,0,0\nBRDA:4,1,0,1\nBRF:2\nBRH:1\n\
+         DA:1,1\nDA:2,1\nDA:3,0\nDA:4,1\nLF:4\nLH:3\nend_of_record\n"
+    );
+
+    runtime
+        .clean_env()
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut "a".as_bytes(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+    let mut merged = runtime.coverage.clone().unwrap();
+    assert!(merged.is_covered(3));
+    merged.merge(&coverage);
+    assert_eq!(merged.profile.count(0), 3);
+
+    // A profiled run exports the same coverage
+    let mut runtime = BrainfuckRuntime::new();
+    runtime.profile = Some(Profile::new());
+    runtime.eof_policy = EofPolicy::Zero;
+    runtime
+        .add_tokens(tokens.clone())
+        .expect(DEFAULT_TEST_ERROR)
+        .run_full_stack(&mut std::io::empty(), &mut std::io::sink())
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(Coverage::from(runtime.profile.unwrap()), coverage);
+}
+
+#[test]
+fn test_spanned_tokens() {
+    let source = String::from("+ comment >\n  [-]");
+    let tokens = Brainfuck::to_spanned_tokens(&source).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(tokens.len(), 5);
+    assert_eq!(tokens[1].span(), 10..11);
+    assert_eq!(
+        tokens[2],
+        SpannedToken {
+            token: BFToken::JumpForwards,
+            start: SourcePosition {
+                offset: 14,
+                line: 2,
+                column: 3,
+            },
+            end: 15,
+        }
+    );
+
+    let source = String::from("Blub. Blub?\n Blub!  Blub!");
+    let tokens = Blub::to_spanned_tokens(&source).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(
+        tokens
+            .iter()
+            .map(|spanned| &source[spanned.span()])
+            .collect::<Vec<&str>>(),
+        vec!["Blub. Blub?", "Blub!  Blub!"]
+    );
+    assert_eq!(tokens[1].start.line, 2);
+    assert_eq!(tokens[1].start.column, 2);
+    assert_eq!(
+        Blub::to_tokens(source).expect(DEFAULT_TEST_ERROR),
+        vec![BFToken::PtrRight, BFToken::CellSubtract]
+    );
+}
+
+#[test]
+fn test_tokenize_reader() {
+    let source = "Ook. Ook. Ook! Ook?\nOook! Ook!\n Ook? Ook!\n";
+    assert_eq!(
+        Ook::tokenize_reader(source.as_bytes()).collect::<Result<Vec<BFToken>, _>>(),
+        Ook::to_tokens(source)
+    );
+    assert_eq!(
+        Brainfuck::tokenize_reader(HELLO_WORLD.as_bytes())
+            .collect::<Result<Vec<BFToken>, _>>()
+            .expect(DEFAULT_TEST_ERROR),
+        Brainfuck::to_tokens(HELLO_WORLD).expect(DEFAULT_TEST_ERROR)
+    );
+
+    let mut tokens = Brainfuck::tokenize_reader("+\n+]".as_bytes());
+    assert_eq!(tokens.next(), Some(Ok(BFToken::CellAdd)));
+    assert_eq!(tokens.next(), Some(Ok(BFToken::CellAdd)));
+    let err = tokens.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::UnbalancedBracket);
+    assert_eq!(err.position.map(|position| position.offset), Some(3));
+    assert_eq!(tokens.next(), None);
+
+    let source = "Blub. Blub.\nBlub! Blub?\n\n Blub.";
+    let streamed: Result<Vec<BFToken>, _> = Blub::tokenize_reader(source.as_bytes()).collect();
+    assert!(streamed.is_err());
+    assert_eq!(streamed, Blub::to_tokens(source));
+
+    let source = "+[\n[-]\n";
+    let streamed: Result<Vec<BFToken>, _> = Brainfuck::tokenize_reader(source.as_bytes()).collect();
+    assert!(streamed.is_err());
+    assert_eq!(streamed, Brainfuck::to_tokens(source));
+
+    // Buffers cut through lexemes and multi-byte characters
+    let source = "Ook. Ook. ✓ Ook! Ook? ✓ Ook! Ook! ✓ Ook? Ook!";
+    for capacity in 1..8 {
+        let reader = std::io::BufReader::with_capacity(capacity, source.as_bytes());
+        assert_eq!(
+            Ook::tokenize_reader(reader).collect::<Result<Vec<BFToken>, _>>(),
+            Ook::to_tokens(source)
+        );
+    }
+    let err = Ook::tokenize_reader(std::io::BufReader::with_capacity(
+        2,
+        "Ook. Ook. Ook.".as_bytes(),
+    ))
+    .last()
+    .unwrap()
+    .unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::DanglingHalfToken);
+    assert_eq!(err.position.map(|position| position.column), Some(11));
+
+    // Tokenizers that only implement `to_spanned_tokens` can be streamed too
+    struct Words;
+    impl Tokenizer for Words {
+        fn to_spanned_tokens(source: &str) -> Result<Vec<SpannedToken>, TokenParseError> {
+            let tokens = Brainfuck::to_spanned_tokens(&source.replace("plus", "+   "))?;
+            if source.trim_end().ends_with(['p', 'l', 'u']) {
+                return Err(TokenParseError::new(
+                    ParseErrorKind::DanglingHalfToken,
+                    None,
+                    source,
+                ));
+            }
+            Ok(tokens)
+        }
+        fn token_to_string(element: BFToken) -> Result<String, TokenParseError> {
+            Brainfuck::token_to_string(element)
+        }
+    }
+    let reader = std::io::BufReader::with_capacity(3, "plus [plus] plus".as_bytes());
+    assert_eq!(
+        Words::tokenize_reader(reader).collect::<Result<Vec<BFToken>, _>>(),
+        Words::to_tokens("plus [plus] plus")
+    );
+    let err = Words::tokenize_reader("plus [plus".as_bytes())
+        .last()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::UnbalancedBracket);
+    assert_eq!(err.position.map(|position| position.offset), Some(5));
+}
+
+#[test]
+fn test_translation() {
+    let tokens = Brainfuck::to_tokens(HELLO_WORLD).expect(DEFAULT_TEST_ERROR);
+
+    let brainfuck = Brainfuck::from_tokens(&tokens);
+    assert!(brainfuck.lines().all(|line| line.len() <= 80));
+    assert_eq!(brainfuck.replace('\n', ""), HELLO_WORLD);
+
+    let ook = Ook::from_tokens(&tokens);
+    assert!(ook.lines().all(|line| line.len() <= 80));
+    assert_eq!(ook.lines().next().map(str::len), Some(79));
+    assert_eq!(Ook::to_tokens(&ook), Ok(tokens.clone()));
+
+    let blub = translate::<Ook, Blub>(&ook).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(
+        translate::<Blub, Brainfuck>(&blub).expect(DEFAULT_TEST_ERROR),
+        brainfuck
+    );
+
+    assert_eq!(
+        DebugBrainfuck::from_tokens(&[BFToken::CellAdd, BFToken::NoOP, BFToken::Debug]),
+        "+#"
+    );
+    assert_eq!(
+        translate::<DebugBrainfuck, Ook>("+#").map_err(|err| err.kind),
+        Err(ParseErrorKind::UnrepresentableToken)
+    );
+}
+
+#[test]
+fn test_substitution_dialect() {
+    let ook = SubstitutionDialect::new([
+        "Ook. Ook.",
+        "Ook! Ook!",
+        "Ook? Ook.",
+        "Ook. Ook?",
+        "Ook! Ook.",
+        "Ook. Ook!",
+        "Ook! Ook?",
+        "Ook? Ook!",
+    ])
+    .expect(DEFAULT_TEST_ERROR);
+    let program = Ook::from_tokens(&Brainfuck::to_tokens(HELLO_WORLD).expect(DEFAULT_TEST_ERROR));
+    assert_eq!(ook.to_tokens(&program), Ook::to_tokens(&program));
+    assert_eq!(ook.from_tokens(&ook.to_tokens(&program).unwrap()), program);
+
+    let spanned = ook
+        .to_spanned_tokens("comment Ook.\n   Ook! Ook!  Ook!")
+        .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(spanned.len(), 2);
+    assert_eq!(spanned[0].span(), 8..20);
+    assert_eq!(spanned[1].start.line, 2);
+
+    // The longest lexeme wins when one starts with another
+    let words =
+        SubstitutionDialect::new(["inc", "dec", "left", "right", "out", "in", "do", "done"])
+            .expect(DEFAULT_TEST_ERROR);
+    assert_eq!(
+        words.to_tokens("in inc do out done"),
+        Ok(vec![
+            BFToken::Input,
+            BFToken::CellAdd,
+            BFToken::JumpForwards,
+            BFToken::Print,
+            BFToken::JumpBackwards
+        ])
+    );
+    assert_eq!(
+        words.to_tokens("done").map_err(|err| err.kind),
+        Err(ParseErrorKind::UnbalancedBracket)
+    );
+    assert_eq!(
+        words
+            .token_to_string(BFToken::Debug)
+            .map_err(|err| err.kind),
+        Err(ParseErrorKind::UnrepresentableToken)
+    );
+
+    // Lexemes match inside of words unless `whole_words` is set
+    assert_eq!(
+        words.to_tokens("window done"),
+        Ok(vec![
+            BFToken::Input,
+            BFToken::JumpForwards,
+            BFToken::JumpBackwards
+        ])
+    );
+    let mut whole = words.clone();
+    whole.whole_words = true;
+    assert_eq!(
+        whole.to_tokens("window in, do_it (do) done"),
+        words.to_tokens("in do done")
+    );
+
+    // Dialects work wherever tokenizers do
+    let letters = SubstitutionDialect::new(["a", "s", "l", "r", "p", "i", "(", ")"])
+        .expect(DEFAULT_TEST_ERROR);
+    let brainfuck = StaticDialect::<Brainfuck>::new();
+    assert_eq!(
+        translate_with(HELLO_WORLD, &brainfuck, &ook),
+        translate::<Brainfuck, Ook>(HELLO_WORLD)
+    );
+    assert_eq!(
+        translate_with("a#", &StaticDialect::<DebugBrainfuck>::new(), &letters)
+            .map_err(|err| err.kind),
+        Err(ParseErrorKind::UnrepresentableToken)
+    );
+    // Streaming keeps the words around lexemes cut off by the end of a buffer
+    let source = "inc xinc inc";
+    assert_eq!(
+        whole.to_tokens(source),
+        Ok(vec![BFToken::CellAdd, BFToken::CellAdd])
+    );
+    for capacity in 1..8 {
+        let reader = std::io::BufReader::with_capacity(capacity, program.as_bytes());
+        assert_eq!(
+            ook.tokenize_reader(reader)
+                .collect::<Result<Vec<BFToken>, _>>(),
+            ook.to_tokens(&program)
+        );
+        let reader = std::io::BufReader::with_capacity(capacity, source.as_bytes());
+        assert_eq!(
+            whole
+                .tokenize_reader(reader)
+                .collect::<Result<Vec<BFToken>, _>>(),
+            whole.to_tokens(source)
+        );
+        let words_source = "window in, xinc do_it (do) indone done";
+        let reader = std::io::BufReader::with_capacity(capacity, words_source.as_bytes());
+        assert_eq!(
+            whole
+                .tokenize_reader(reader)
+                .collect::<Result<Vec<BFToken>, _>>(),
+            whole.to_tokens(words_source)
+        );
+    }
+
+    assert_eq!(
+        SubstitutionDialect::new(["+", "-", "<", ">", ".", ",", " ", "]"]),
+        Err(DialectError::EmptyLexeme(BFToken::JumpForwards))
+    );
+    assert_eq!(
+        SubstitutionDialect::new(["a", "b", "c", "d", "e", "f", "g", "a"]),
+        Err(DialectError::DuplicateLexeme(String::from("a")))
+    );
+}
+
+#[cfg(all(feature = "toml", feature = "json"))]
+#[test]
+fn test_dialect_spec() {
+    let toml_spec = r##"
+        name = "Meow"
+        case_insensitive = true
+        line_comment = "#"
+        block_comment = ["(=^", "^=)"]
+
+        [lexemes]
+        cell_add = "meow"
+        cell_subtract = "hiss"
+        ptr_left = "paw"
+        ptr_right = "pounce"
+        print = "purr"
+        input = "sniff"
+        jump_forwards = "nap"
+        jump_backwards = "wake"
+    "##;
+    let spec = DialectSpec::from_toml(toml_spec).expect(DEFAULT_TEST_ERROR);
+    let json_spec = serde_json::to_string(&spec).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(DialectSpec::from_json(&json_spec), Ok(spec.clone()));
+
+    let path = std::env::temp_dir().join(format!("libbfi-meow-{}.toml", std::process::id()));
+    std::fs::write(&path, toml_spec).expect(DEFAULT_TEST_ERROR);
+    let meow = SubstitutionDialect::load(&path).expect(DEFAULT_TEST_ERROR);
+    std::fs::remove_file(&path).expect(DEFAULT_TEST_ERROR);
+    assert_eq!(meow.name.as_deref(), Some("Meow"));
+
+    let program =
+        "MEOW Meow # purr purr\nnap pounce meow (=^ hiss\nhiss ^=) paw hiss wake pounce purr";
+    assert_eq!(meow.to_tokens(program), Brainfuck::to_tokens("++[>+<-]>."));
+    let reader = std::io::BufReader::with_capacity(2, program.as_bytes());
+    assert_eq!(
+        meow.tokenize_reader(reader)
+            .collect::<Result<Vec<BFToken>, _>>(),
+        meow.to_tokens(program)
+    );
+    assert_eq!(
+        meow.from_tokens(&Brainfuck::to_tokens("+[-]").expect(DEFAULT_TEST_ERROR)),
+        "meow nap hiss wake"
+    );
+
+    assert!(matches!(
+        DialectSpec::from_toml("name = \"Meow\"\n[lexemes]\ncell_add = \"meow\""),
+        Err(DialectError::InvalidSpec(_))
+    ));
+    let mut shouting = spec.clone();
+    shouting.lexemes.print = String::from("MEOW");
+    assert_eq!(
+        SubstitutionDialect::from_spec(shouting),
+        Err(DialectError::DuplicateLexeme(String::from("MEOW")))
+    );
+    // Lexemes hidden by a comment marker could never be matched
+    let mut hashtag = spec.clone();
+    hashtag.lexemes.print = String::from("#purr");
+    assert!(matches!(
+        SubstitutionDialect::from_spec(hashtag),
+        Err(DialectError::InvalidSpec(_))
+    ));
+    let mut smiling = spec.clone();
+    smiling.lexemes.input = String::from("(=^.^=)");
+    assert!(matches!(
+        SubstitutionDialect::from_spec(smiling),
+        Err(DialectError::InvalidSpec(_))
+    ));
+    assert!(matches!(
+        SubstitutionDialect::load("missing-meow.toml"),
+        Err(DialectError::Unreadable(_))
+    ));
+
+    // The extension is checked before the file is read
+    let path = std::env::temp_dir().join(format!("libbfi-meow-{}.yaml", std::process::id()));
+    std::fs::write(&path, toml_spec).expect(DEFAULT_TEST_ERROR);
+    let result = SubstitutionDialect::load(&path);
+    std::fs::remove_file(&path).expect(DEFAULT_TEST_ERROR);
+    assert!(
+        matches!(result, Err(DialectError::InvalidSpec(reason)) if reason.starts_with("unsupported spec format"))
+    );
+    assert!(matches!(
+        SubstitutionDialect::load("missing-meow.yaml"),
+        Err(DialectError::InvalidSpec(_))
+    ));
+}